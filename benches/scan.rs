@@ -0,0 +1,129 @@
+//! Benchmarks for loading and discovering cookie databases and for TUI
+//! searches, run with `cargo bench`. The fixtures are generated in a
+//! temporary directory on each run.
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use rokie::config::{Config, CONFIG};
+use rokie::cookie_db::CookieDB;
+use rokie::search::SearchIndex;
+use rokie::util::{cookie_dbs_from_profiles, DbType};
+
+/// Number of cookies in the fixture for `load_cookies`
+const COOKIE_COUNT: usize = 50_000;
+/// Number of profiles (and unrelated directories) in the scanned tree
+const PROFILE_COUNT: usize = 50;
+/// Number of items in the searched list
+const ITEM_COUNT: usize = 100_000;
+
+fn fixture_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "rokie-bench-{}-{}",
+        name,
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Create a Firefox cookie database with `count` cookies
+fn create_firefox_db(path: &Path, count: usize) {
+    let mut conn = rusqlite::Connection::open(path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE moz_cookies (id INTEGER PRIMARY KEY,
+            originAttributes TEXT NOT NULL DEFAULT '', name TEXT,
+            value TEXT, host TEXT, path TEXT, expiry INTEGER,
+            lastAccessed INTEGER, creationTime INTEGER,
+            isSecure INTEGER, isHttpOnly INTEGER, sameSite INTEGER);",
+    )
+    .unwrap();
+    let tx = conn.transaction().unwrap();
+    {
+        let mut stmt = tx
+            .prepare(
+                "INSERT INTO moz_cookies (name, value, host, path, expiry,
+                    lastAccessed, creationTime, isSecure, isHttpOnly,
+                    sameSite)
+                 VALUES (?1, ?2, ?3, '/', 1900000000,
+                    1710000000000000, 1600000000000000, 1, 0, 1)",
+            )
+            .unwrap();
+        for i in 0..count {
+            stmt.execute(rusqlite::params![
+                format!("name{}", i % 20),
+                "x".repeat(64),
+                format!(".site{}.example.com", i / 20),
+            ])
+            .unwrap();
+        }
+    }
+    tx.commit().unwrap();
+}
+
+fn bench_load_cookies(c: &mut Criterion) {
+    let dir = fixture_dir("load");
+    let path = dir.join("cookies.sqlite");
+    create_firefox_db(&path, COOKIE_COUNT);
+
+    c.bench_function("load_cookies", |b| {
+        b.iter(|| {
+            let mut cdb = CookieDB::new(path.clone(), DbType::Firefox);
+            cdb.load_cookies().unwrap();
+            cdb
+        })
+    });
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+fn bench_scan(c: &mut Criterion) {
+    // The search directories are relative to $HOME
+    let home = fixture_dir("scan");
+    let profiles = home.join(".mozilla/firefox");
+    for i in 0..PROFILE_COUNT {
+        let profile = profiles.join(format!("p{i}"));
+        std::fs::create_dir_all(profile.join("cache/entries")).unwrap();
+        create_firefox_db(&profile.join("cookies.sqlite"), 10);
+        std::fs::write(profile.join("cache/entries/data"), "").unwrap();
+    }
+    std::env::set_var("HOME", &home);
+
+    c.bench_function("cookie_dbs_from_profiles", |b| {
+        b.iter(|| {
+            let mut cookie_dbs = HashSet::new();
+            cookie_dbs_from_profiles(&mut cookie_dbs);
+            assert_eq!(cookie_dbs.len(), PROFILE_COUNT);
+            cookie_dbs
+        })
+    });
+    std::fs::remove_dir_all(home).unwrap();
+}
+
+/// The positions of the items in a list that match a query, which is how
+/// the TUI collects its search matches
+fn bench_set_matches(c: &mut Criterion) {
+    let items: Vec<String> = (0..ITEM_COUNT)
+        .map(|i| format!(".site{i}.example.com"))
+        .collect();
+    let index = SearchIndex::new(items.iter().map(|s| s.as_str()));
+
+    c.bench_function("set_matches", |b| {
+        b.iter(|| {
+            let matches = index.matching("site99");
+            (0..items.len())
+                .filter(|i| matches.contains(items[*i].as_str()))
+                .collect::<Vec<usize>>()
+        })
+    });
+}
+
+fn setup(c: &mut Criterion) {
+    CONFIG.get_or_init(Config::default);
+    bench_load_cookies(c);
+    bench_scan(c);
+    bench_set_matches(c);
+}
+
+criterion_group!(benches, setup);
+criterion_main!(benches);
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::cookie::Cookie;
+use crate::cookie_db::CookieDB;
+
+/// Expiries further away than this (in seconds) are reported
+const MAX_EXPIRY: i64 = 365 * 86400;
+/// Browsers only accept cookies where the name and value together are
+/// at most this many bytes
+const MAX_COOKIE_SIZE: usize = 4096;
+
+/// Findings are ranked from `High` to `Low`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    High,
+    Medium,
+    Low,
+}
+
+/// A cookie (or a set of duplicate rows) that failed one of the checks
+#[derive(Debug, Serialize)]
+pub struct Finding<'a> {
+    pub severity: Severity,
+    pub check: &'static str,
+    pub host: &'a str,
+    pub name: &'a str,
+    pub path: &'a str,
+    pub detail: String,
+}
+
+impl<'a> Finding<'a> {
+    fn new(
+        severity: Severity,
+        check: &'static str,
+        c: &'a Cookie,
+        detail: String,
+    ) -> Self {
+        Finding {
+            severity,
+            check,
+            host: &c.host,
+            name: &c.name,
+            path: &c.path,
+            detail,
+        }
+    }
+}
+
+/// Check the loaded cookies of a profile for risky or unusual attributes,
+/// the findings are sorted by severity. `now` is a UNIX epoch timestamp.
+pub fn audit(cookie_db: &CookieDB, now: i64) -> Vec<Finding<'_>> {
+    let mut findings = vec![];
+
+    // Sent with cross-site requests over plain HTTP, an unspecified
+    // SameSite (-1) is treated as `None` in the same way as in the output
+    for c in cookie_db.cookies_matching(|c| c.samesite <= 0 && !c.secure) {
+        let detail = "SameSite=None without Secure".to_string();
+        findings.push(Finding::new(
+            Severity::High,
+            "insecure-cross-site",
+            c,
+            detail,
+        ));
+    }
+    for c in cookie_db.cookies_matching(|c| !c.prefix_violations().is_empty()) {
+        let detail = c.prefix_violations().join(", ");
+        findings.push(Finding::new(Severity::High, "prefix", c, detail));
+    }
+    for c in cookie_db
+        .cookies_matching(|c| c.name.len() + c.value.len() > MAX_COOKIE_SIZE)
+    {
+        let detail = format!("{} bytes", c.name.len() + c.value.len());
+        findings.push(Finding::new(Severity::Medium, "oversized", c, detail));
+    }
+    for c in cookie_db
+        .cookies_matching(|c| !c.is_session() && c.expiry - now > MAX_EXPIRY)
+    {
+        let detail = format!("expires in {} days", (c.expiry - now) / 86400);
+        findings.push(Finding::new(Severity::Low, "long-expiry", c, detail));
+    }
+
+    // Rows from different Firefox containers are not duplicates
+    let mut rows: HashMap<(&str, &str, &str, &str), Vec<&Cookie>> =
+        HashMap::new();
+    for c in cookie_db.cookies.iter() {
+        rows.entry((&c.host, &c.name, &c.path, &c.origin_attributes))
+            .or_default()
+            .push(c);
+    }
+    for duplicates in rows.values().filter(|r| r.len() > 1) {
+        let detail = format!("{} rows", duplicates.len());
+        findings.push(Finding::new(
+            Severity::Medium,
+            "duplicate",
+            duplicates[0],
+            detail,
+        ));
+    }
+
+    findings.sort_by(|a, b| {
+        (a.severity, a.host, a.name, a.check)
+            .cmp(&(b.severity, b.host, b.name, b.check))
+    });
+    findings
+}
+
+/// One line per finding followed by the number of findings per severity
+pub fn format_findings(findings: &[Finding]) -> String {
+    let mut output = String::new();
+    for f in findings {
+        let severity = format!("{:?}", f.severity).to_uppercase();
+        output += &format!(
+            "{:<6}  {:<19}  {} {} {}: {}\n",
+            severity, f.check, f.host, f.name, f.path, f.detail
+        );
+    }
+    let count = |s| findings.iter().filter(|f| f.severity == s).count();
+    output += &format!(
+        "{} finding(s): {} high, {} medium, {} low\n",
+        findings.len(),
+        count(Severity::High),
+        count(Severity::Medium),
+        count(Severity::Low)
+    );
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::{audit, format_findings};
+    use crate::config::{Config, CONFIG};
+    use crate::cookie_db::CookieDB;
+    use crate::fixtures::{TempDir, CHROME_SCHEMA, FIREFOX_SCHEMA};
+    use crate::util::DbType;
+
+    /// The time of the audit, the expiries of the fixtures are relative to it
+    const NOW: i64 = 1_700_000_000;
+
+    #[test]
+    fn test_audit_samesite() {
+        CONFIG.get_or_init(Config::default);
+        let dir = TempDir::new("audit-samesite");
+        let path = dir.sqlite(
+            "Cookies",
+            &format!(
+                "{CHROME_SCHEMA}
+                INSERT INTO cookies VALUES
+                    (0, '.a.com', 'none', '', '', '/', 0, 0, 0, 0, 0),
+                    (0, '.a.com', 'unspecified', '', '', '/', 0, 0, 0, 0, -1),
+                    (0, '.a.com', 'secure', '', '', '/', 0, 1, 0, 0, 0),
+                    (0, '.a.com', 'lax', '', '', '/', 0, 0, 0, 0, 1);"
+            ),
+        );
+        let mut cdb = CookieDB::new(path, DbType::Chrome);
+        cdb.load_cookies().unwrap();
+
+        // Chrome stores an unspecified SameSite as -1, which is listed
+        // as `None` and audited as such
+        let unspecified = cdb
+            .cookies
+            .iter()
+            .find(|c| c.name == "unspecified")
+            .unwrap();
+        assert_eq!(unspecified.samesite_str(), "None");
+
+        let findings = audit(&cdb, NOW);
+        let names: Vec<(&str, &str)> =
+            findings.iter().map(|f| (f.check, f.name)).collect();
+        assert_eq!(
+            names,
+            vec![
+                ("insecure-cross-site", "none"),
+                ("insecure-cross-site", "unspecified")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_audit() {
+        CONFIG.get_or_init(Config::default);
+        let dir = TempDir::new("audit");
+        // Rows from different containers are not duplicates
+        let path = dir.sqlite(
+            "cookies.sqlite",
+            &format!(
+                "{FIREFOX_SCHEMA}
+                INSERT INTO moz_cookies VALUES
+                    (1, '', '__Host-tok', '', 'b.com', '/app', 0, 0, 0, 1, 0, 1),
+                    (2, '', 'big', hex(zeroblob(2100)), 'b.com', '/',
+                     0, 0, 0, 1, 0, 1),
+                    (3, '', 'long', '', 'c.com', '/', 2000000000000000,
+                     0, 0, 1, 0, 1),
+                    (4, '', 'dup', 'a', 'd.com', '/', 0, 0, 0, 1, 0, 1),
+                    (5, '', 'dup', 'b', 'd.com', '/', 0, 0, 0, 1, 0, 1),
+                    (6, '^userContextId=1', 'dup', 'c', 'd.com', '/',
+                     0, 0, 0, 1, 0, 1),
+                    (7, '', 'ok', '', 'e.com', '/', 0, 0, 0, 1, 0, 1);"
+            ),
+        );
+        let mut cdb = CookieDB::new(path, DbType::Firefox);
+        cdb.load_cookies().unwrap();
+
+        // Sorted by severity and then by host
+        let findings = audit(&cdb, NOW);
+        let checks: Vec<(&str, &str)> =
+            findings.iter().map(|f| (f.check, f.host)).collect();
+        assert_eq!(
+            checks,
+            vec![
+                ("prefix", "b.com"),
+                ("oversized", "b.com"),
+                ("duplicate", "d.com"),
+                ("long-expiry", "c.com")
+            ]
+        );
+
+        let output = format_findings(&findings);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("HIGH    prefix "));
+        assert!(lines[1].ends_with("b.com big /: 4203 bytes"));
+        assert!(lines[2].ends_with("d.com dup /: 2 rows"));
+        assert_eq!(lines[4], "4 finding(s): 1 high, 2 medium, 1 low");
+
+        assert_eq!(
+            format_findings(&[]),
+            "0 finding(s): 0 high, 0 medium, 0 low\n"
+        );
+    }
+}
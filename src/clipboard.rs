@@ -0,0 +1,206 @@
+use std::env::{self, consts};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use crate::config::Config;
+use crate::error::Error;
+
+/// A backend that can place text on the system clipboard
+pub trait Clipboard {
+    /// Name of the backend, shown in debug output
+    fn name(&self) -> &str;
+    fn copy(&self, content: &str) -> Result<(), Error>;
+}
+
+/// Pipes the content to the stdin of an external program,
+/// e.g. `pbcopy` or `xsel`
+pub struct CommandClipboard {
+    program: &'static str,
+    args: &'static [&'static str],
+}
+
+impl Clipboard for CommandClipboard {
+    fn name(&self) -> &str {
+        self.program
+    }
+
+    fn copy(&self, content: &str) -> Result<(), Error> {
+        let mut p = Command::new(self.program)
+            .args(self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => Error::MissingProgram(self.program),
+                _ => Error::Io(e),
+            })?;
+
+        p.stdin.take().unwrap().write_all(content.as_bytes())?;
+        p.wait()?;
+        Ok(())
+    }
+}
+
+/// Emits an OSC 52 escape sequence to the terminal, which sets the clipboard
+/// of the local machine in terminals that support it, even over SSH.
+pub struct Osc52;
+
+impl Osc52 {
+    pub fn sequence(content: &str) -> String {
+        format!("\x1b]52;c;{}\x07", base64(content.as_bytes()))
+    }
+}
+
+impl Clipboard for Osc52 {
+    fn name(&self) -> &str {
+        "osc52"
+    }
+
+    fn copy(&self, content: &str) -> Result<(), Error> {
+        let mut stdout = io::stdout();
+        stdout.write_all(Osc52::sequence(content).as_bytes())?;
+        Ok(stdout.flush()?)
+    }
+}
+
+/// Used when no clipboard is available, copying is silently ignored
+pub struct NoClipboard;
+
+impl Clipboard for NoClipboard {
+    fn name(&self) -> &str {
+        "none"
+    }
+
+    fn copy(&self, _: &str) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Select a clipboard backend for the current environment
+pub fn detect() -> Box<dyn Clipboard> {
+    select(consts::OS, Config::global().osc52, |var| {
+        env::var_os(var).is_some()
+    })
+}
+
+/// Select a clipboard backend for `os` based on which of the environment
+/// variables checked with `has_env` are set. Over SSH, the local clipboard
+/// belongs to another machine so OSC 52 is the only option. Inside tmux,
+/// the paste buffer of tmux is used if there is no other clipboard.
+pub fn select<F: Fn(&str) -> bool>(
+    os: &str,
+    osc52: bool,
+    has_env: F,
+) -> Box<dyn Clipboard> {
+    let fallback = || -> Box<dyn Clipboard> {
+        if has_env("TMUX") {
+            Box::new(CommandClipboard {
+                program: "tmux",
+                args: &["load-buffer", "-"],
+            })
+        } else {
+            Box::new(NoClipboard)
+        }
+    };
+    if has_env("SSH_CONNECTION") {
+        return if osc52 { Box::new(Osc52) } else { fallback() };
+    }
+    let (program, args): (&'static str, &'static [&'static str]) = match os {
+        "macos" => ("/usr/bin/pbcopy", &[]),
+        "android" => ("termux-clipboard-set", &[]),
+        "linux" | "freebsd" => {
+            if has_env("TERMUX_VERSION") {
+                ("termux-clipboard-set", &[])
+            } else if has_env("WAYLAND_DISPLAY") {
+                ("wl-copy", &[])
+            } else if has_env("DISPLAY") {
+                ("xsel", &["-i", "-b"])
+            } else if has_env("WSL_DISTRO_NAME") {
+                ("clip.exe", &[])
+            } else {
+                return fallback();
+            }
+        }
+        "windows" => ("clip.exe", &[]),
+        _ => return fallback(),
+    };
+    Box::new(CommandClipboard { program, args })
+}
+
+/// Standard base64 encoding with padding
+fn base64(data: &[u8]) -> String {
+    const CHARS: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(CHARS[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Records copied content instead of using the system clipboard
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockClipboard {
+    pub copied: std::rc::Rc<std::cell::RefCell<Vec<String>>>,
+}
+
+#[cfg(test)]
+impl Clipboard for MockClipboard {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn copy(&self, content: &str) -> Result<(), Error> {
+        self.copied.borrow_mut().push(content.to_string());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clipboard::{base64, select, Osc52};
+
+    #[test]
+    fn test_select() {
+        let name = |os: &str, vars: &[&str]| {
+            select(os, false, |v| vars.contains(&v)).name().to_string()
+        };
+        // OSC 52 is only used over SSH if enabled
+        assert_eq!(name("linux", &["SSH_CONNECTION", "DISPLAY"]), "none");
+        let remote = select("linux", true, |v| v == "SSH_CONNECTION");
+        assert_eq!(remote.name(), "osc52");
+        let local = select("linux", true, |v| v == "DISPLAY");
+        assert_eq!(local.name(), "xsel");
+        assert_eq!(name("linux", &["DISPLAY", "WAYLAND_DISPLAY"]), "wl-copy");
+        assert_eq!(name("linux", &["DISPLAY"]), "xsel");
+        assert_eq!(name("linux", &["WSL_DISTRO_NAME"]), "clip.exe");
+        assert_eq!(name("linux", &[]), "none");
+        assert_eq!(name("linux", &["TMUX"]), "tmux");
+        assert_eq!(name("linux", &["TMUX", "DISPLAY"]), "xsel");
+        assert_eq!(name("linux", &["TMUX", "SSH_CONNECTION"]), "tmux");
+        assert_eq!(name("macos", &[]), "/usr/bin/pbcopy");
+    }
+
+    #[test]
+    fn test_osc52() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(Osc52::sequence("token"), "\x1b]52;c;dG9rZW4=\x07");
+    }
+}
@@ -1,21 +1,69 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use clap::builder::PossibleValuesParser;
 use clap::{Parser, Subcommand};
 use once_cell::sync::OnceCell;
 use phf::phf_map;
 
+use crate::export::{ExportFormat, EXPORT_FORMATS};
+use crate::util::{app_dir, parse_duration, DbType};
+
 //== Global constants ==//
-pub const ENCRYPTED_VALUE: &'static str = "********";
-pub const ALL_FIELDS: &'static str = "All";
+pub const ENCRYPTED_VALUE: &str = "********";
+/// Cookie name prefixes that restrict how a cookie can be set
+pub const COOKIE_PREFIXES: &[&str] = &["__Host-", "__Secure-"];
+/// File in a Firefox profile with the names of each container
+pub const FIREFOX_CONTAINERS: &str = "containers.json";
+pub const ALL_FIELDS: &str = "All";
 pub const NO_SELECTION: usize = 9999999;
-pub const DEBUG_LOG: &'static str = "rokie.log";
+/// Epoch that timestamps which are negative or out of range are mapped to
+pub const INVALID_TIMESTAMP: i64 = -1;
+/// Latest supported timestamp, 9999-12-31T23:59:59Z
+pub const MAX_TIMESTAMP: i64 = 253_402_300_799;
+/// Seconds between the Chrome epoch (1601-01-01) and the UNIX epoch
+pub const CHROME_EPOCH_OFFSET: i64 = 11_644_473_600;
+/// Debug log of the TUI, inside `STATE_DIR`
+pub const DEBUG_LOG: &str = "rokie.log";
+/// Environment variable that overrides the path of `DEBUG_LOG`, if set,
+/// messages from `debugln!` and `errln!` are also written to the log
+pub const DEBUG_LOG_ENV: &str = "ROKIE_LOG";
 pub const TUI_PRIMARY_COLOR: u8 = 111;
 pub const TUI_TEXT_TRUNCATE_LIM: usize = 48;
-pub const TUI_SEARCH: &'static str = "Search:";
-pub const SQLITE_FILE_ID: &'static str = "SQLite format 3";
+pub const TUI_SEARCH: &str = "Search:";
+pub const TUI_EXPORT: &str = "Export to:";
+pub const TUI_ALL_PROFILES: &str = "All profiles";
+/// Number of seconds that a status message is shown in the footer
+pub const TUI_STATUS_DURATION: u64 = 2;
+/// Maximum number of digits in a count prefix, e.g. `10j`
+pub const TUI_COUNT_PREFIX_LIM: usize = 4;
+/// Milliseconds to wait for the second key of a chord, e.g. `dd`
+pub const TUI_CHORD_TIMEOUT: u64 = 1000;
+/// Milliseconds without writes before a watched database is reloaded
+pub const TUI_WATCH_DEBOUNCE: u64 = 500;
+/// Number of lines scrolled with PageUp/PageDown in the value popup
+pub const TUI_POPUP_PAGE: u16 = 10;
+/// Terminal width below which the TUI only shows the active pane
+pub const TUI_COMPACT_WIDTH: u16 = 90;
+/// Characters of the labels in quick-jump mode, in order of preference
+pub const TUI_JUMP_CHARS: &str = "asdfghjklqwertyuiopzxcvbnm";
+/// Directory of rokie inside each XDG base directory
+pub const APP_DIR: &str = "rokie";
+/// Base directory for configuration files and its default relative to
+/// $HOME if the environment variable is unset
+pub const CONFIG_DIR: (&str, &str) = ("XDG_CONFIG_HOME", ".config");
+/// Base directory for logs and other state that persists across runs
+pub const STATE_DIR: (&str, &str) = ("XDG_STATE_HOME", ".local/state");
+/// Keymap for the TUI inside `CONFIG_DIR`, used if it exists
+pub const KEYMAP_FILE: &str = "keymap";
+/// Domains that are pinned in the TUI, inside `CONFIG_DIR`
+pub const PINNED_FILE: &str = "pinned";
+pub const SQLITE_FILE_ID: &str = "SQLite format 3";
 
-pub const DB_NAMES: &'static [&'static str] =
+pub const DB_NAMES: &[&str] =
     &["Cookies", "Safe Browsing Cookies", "cookies.sqlite"];
 
-pub const SEARCH_DIRS: &'static [&'static str] = &[
+pub const SEARCH_DIRS: &[&str] = &[
     ".mozilla/firefox",
     ".config/chromium",
     ".config/BraveSoftware/Brave-Browser",
@@ -28,6 +76,48 @@ pub const SEARCH_DIRS: &'static [&'static str] = &[
     "Library/Application Support/BraveSoftware/Brave-Browser",
 ];
 
+/// The type of database of each browser, its executable names (lowercase,
+/// without `.exe`) and the ID of its Flatpak, which is only part of the
+/// command line of the `flatpak` and `bwrap` processes that run the sandbox
+pub const BROWSER_PROCESSES: &[(&str, DbType, &[&str], &str)] = &[
+    (
+        "Firefox",
+        DbType::Firefox,
+        &["firefox", "firefox-bin", "firefox-esr"],
+        "org.mozilla.firefox",
+    ),
+    (
+        "Chromium",
+        DbType::Chrome,
+        &["chromium", "chromium-browser"],
+        "org.chromium.Chromium",
+    ),
+    (
+        "Chrome",
+        DbType::Chrome,
+        &[
+            "chrome",
+            "google-chrome",
+            "google-chrome-stable",
+            "google chrome",
+        ],
+        "com.google.Chrome",
+    ),
+    (
+        "Brave",
+        DbType::Chrome,
+        &["brave", "brave-browser", "brave browser"],
+        "com.brave.Browser",
+    ),
+];
+pub const FLATPAK_PROCESSES: &[&str] = &["flatpak", "bwrap"];
+
+/// Columns of newer Chrome schemas that are read if they exist, in the
+/// order of the `priority`, `source_scheme`, `source_port` and `persistent`
+/// cookie fields
+pub const CHROME_OPTIONAL_FIELDS: &[&str] =
+    &["priority", "source_scheme", "source_port", "is_persistent"];
+
 /// A constant hash map with keys representing each valid Cookie field.
 /// Each key maps to a tuple that contains the name of the Chrome and
 /// Firefox version of the corresponding field.
@@ -67,6 +157,46 @@ enum SubArgs {
         /// Only include entries matching a specific domain name
         #[clap(short, long, default_value_t)]
         domain: String,
+
+        /// Only include cookies that were accessed within a duration,
+        /// e.g. `7d`, `24h` or `1w2d`
+        #[clap(long, value_parser = parse_duration)]
+        since: Option<i64>,
+
+        /// Ordering of the cookies, `name` keeps the cookies of each
+        /// profile together while `recent` places the most recently
+        /// accessed cookies from all profiles first
+        #[clap(short, long, default_value = "name",
+               value_parser = ["name", "recent"])]
+        sort: String,
+
+        /// Output at most this many cookies from all profiles, after sorting
+        #[clap(long)]
+        limit: Option<usize>,
+
+        /// Output format, see --list-formats
+        #[clap(long, default_value = "fields",
+               value_parser = PossibleValuesParser::new(
+                   EXPORT_FORMATS.iter().map(|(name, _)| *name)))]
+        format: String,
+
+        /// List valid formats for the --format option
+        #[clap(long, takes_value = false)]
+        list_formats: bool,
+
+        /// Write output to a file instead of stdout,
+        /// parent directories are created as needed
+        #[clap(short, long, default_value_t)]
+        output: String,
+
+        /// Overwrite the --output file if it already exists
+        #[clap(long)]
+        force: bool,
+
+        /// Replace the value of each cookie with a placeholder that only
+        /// includes its length, e.g. `<REDACTED:len=32>`
+        #[clap(long)]
+        anonymize: bool,
     },
     /// Remove cookies non-interactively
     Clean {
@@ -74,12 +204,134 @@ enum SubArgs {
         #[clap(short, long, default_value_t)]
         whitelist: String,
 
+        /// Only remove cookies that have not been accessed
+        /// in the given number of days
+        #[clap(long)]
+        prune_older_than: Option<u32>,
+
         /// Apply changes
         #[clap(short, long)]
         apply: bool,
     },
+    /// Replace a substring in the values of the cookies from a domain,
+    /// e.g. to toggle feature flags during development
+    Replace {
+        /// Domain of the cookies to modify (subdomains are not included)
+        domain: String,
+
+        /// Substring to replace in each cookie value
+        pattern: String,
+
+        /// Replacement for every occurrence of the pattern
+        replacement: String,
+    },
+    /// Compare the cookies of two profiles
+    Diff {
+        /// Profile identifier (see --profile) or path to a cookie database
+        profile_a: String,
+
+        /// Profile identifier (see --profile) or path to a cookie database
+        profile_b: String,
+
+        /// Output the differences as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Report cookies with risky or unusual attributes, e.g. cross-site
+    /// cookies without `Secure` or expiries more than a year away,
+    /// ranked by severity
+    Audit {
+        /// Profile identifier (see --profile) or path to a cookie database,
+        /// every profile is audited if omitted
+        profile: Option<String>,
+
+        /// Output the findings as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Print the cookies with a host or name that contains a pattern,
+    /// one `profile<TAB>host<TAB>name` line per cookie
+    Grep {
+        /// Substring to search for
+        pattern: String,
+
+        /// Match the pattern case-insensitively
+        #[clap(short, long)]
+        ignore_case: bool,
+
+        /// Also match the pattern against cookie values
+        #[clap(long)]
+        values: bool,
+
+        /// Only include cookies that were accessed within a duration,
+        /// e.g. `7d`, `24h` or `1w2d`
+        #[clap(long, value_parser = parse_duration)]
+        since: Option<i64>,
+
+        /// Ordering of the cookies, `name` keeps the cookies of each
+        /// profile together while `recent` places the most recently
+        /// accessed cookies from all profiles first
+        #[clap(short, long, default_value = "name",
+               value_parser = ["name", "recent"])]
+        sort: String,
+
+        /// Print at most this many cookies from all profiles, after sorting
+        #[clap(long)]
+        limit: Option<usize>,
+    },
+    /// Print the sorted, distinct domains of all profiles, without
+    /// loading the cookies themselves
+    Domains,
     /// Interactive view of cookies across all browsers
-    Tui {},
+    Tui {
+        /// Initial ordering of domains and cookies, `recent` places the
+        /// most recently accessed entries first
+        #[clap(short, long, default_value = "name",
+               value_parser = ["name", "recent"])]
+        sort: String,
+
+        /// Ordering of the profiles pane, `modified` places the profiles
+        /// with the most recently modified database first
+        #[clap(long, default_value = "name",
+               value_parser = ["name", "modified"])]
+        profile_order: String,
+
+        /// Fields that a search in the cookies pane is matched against,
+        /// a query can override this with a `n:`, `v:` or `b:` prefix
+        #[clap(long, default_value = "names",
+               value_parser = ["names", "values", "both"])]
+        search_scope: String,
+
+        /// Start with a search for profiles with a path that contains the
+        /// given substring, other profiles are dimmed until the search is
+        /// cleared
+        #[clap(long)]
+        filter: Option<String>,
+
+        /// Start without a selected profile, e.g. to search before picking
+        /// one. The fields of a cookie are only highlighted after moving
+        /// to them.
+        #[clap(long)]
+        no_auto_select: bool,
+
+        /// Reload the selected profile whenever the browser writes to its
+        /// database, e.g. to follow the cookies that are set during a
+        /// login. Combine with --snapshot while the browser is running.
+        #[clap(long)]
+        watch: bool,
+
+        /// Keymap file to use instead of $XDG_CONFIG_HOME/rokie/keymap,
+        /// each line binds an action to one or more keys, e.g. `delete = d`
+        #[clap(long)]
+        keymap: Option<String>,
+
+        /// Copy to the clipboard with an OSC 52 escape sequence when
+        /// running over SSH. The sequence is ignored by terminals that
+        /// do not support it, copying is disabled over SSH otherwise
+        /// unless rokie runs inside tmux.
+        #[clap(long)]
+        osc52: bool,
+    },
 }
 
 #[derive(Parser, Debug)]
@@ -92,7 +344,9 @@ enum SubArgs {
 /// The `value_parser` trait is required to access an option from the `args`
 /// object, this is not usable for subcommands.
 pub struct Args {
-    /// Output debugging information, writes to `rokie.log` when TUI is active
+    /// Output debugging information, writes to
+    /// `$XDG_STATE_HOME/rokie/rokie.log` when TUI is active.
+    /// The log path can be changed with `ROKIE_LOG`.
     #[clap(short, long)]
     debug: bool,
 
@@ -100,17 +354,140 @@ pub struct Args {
     #[clap(long)]
     nocolor: bool,
 
+    /// Suppress informational and debug messages, errors are still shown
+    #[clap(short, long)]
+    quiet: bool,
+
+    /// Do not write any modifications to the cookie databases.
+    /// Deletions in the TUI are only applied to the current view.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Perform destructive operations without asking for confirmation,
+    /// i.e. `clean` behaves as if --apply was passed and prompts in the
+    /// TUI are accepted. Modifications are still skipped with --dry-run.
+    #[clap(short, long)]
+    yes: bool,
+
+    /// Continue even if the browser is running. The browser can overwrite
+    /// or corrupt the database if it writes to it at the same time.
+    #[clap(long)]
+    ignore_running: bool,
+
+    /// Append a line for each modification of a cookie database to the
+    /// given file, modifications are aborted if the log cannot be written
+    #[clap(long)]
+    audit_log: Option<String>,
+
+    /// Only print the number of cookies that `clean` or --delete-stdin
+    /// would remove from each profile and in total, nothing is deleted
+    #[clap(long)]
+    count_only: bool,
+
+    /// Exit with a non-zero status if --count-only finds
+    /// no cookies to remove
+    #[clap(long, requires = "count-only")]
+    fail_if_empty: bool,
+
+    /// Follow symbolic links when searching for browser profiles.
+    /// Symlink cycles are skipped, but links into large or unrelated
+    /// directory trees can make the search slow and may include
+    /// unrelated cookie databases.
+    #[clap(long)]
+    follow_symlinks: bool,
+
+    /// Check every SQLite database found when searching for browser
+    /// profiles, not only files with the usual names of cookie databases.
+    /// This finds renamed or backed up databases, e.g. `Cookies.bak`,
+    /// but makes the search slower.
+    #[clap(long, conflicts_with = "file")]
+    deep_scan: bool,
+
+    /// Search for cookie databases under this directory instead of the
+    /// default browser locations, e.g. a profile from a backup or a
+    /// mounted disk image
+    #[clap(long, conflicts_with = "file")]
+    profile_dir: Option<String>,
+
+    /// Include cookie databases that are world-writable or inside a
+    /// world-writable directory. These are skipped by default since any
+    /// user could have placed or modified them.
+    #[clap(long)]
+    allow_insecure: bool,
+
+    /// File that maps fields to the column names of a nonstandard cookie
+    /// database, one `browser.Field = column` entry per line, e.g.
+    /// `chrome.Host = host`. Unmapped fields use the default columns.
+    #[clap(long)]
+    pub column_map: Option<String>,
+
     /// Only include entries from a specific browser profile.
-    /// Any unique part of the path to profile can be used as an identifier
-    /// e.g. `-p Brave` can be resolved to
-    /// `~/.config/BraveSoftware/Brave-Browser/Default`
+    /// The name of a profile directory, e.g. `-p "Profile 1"`, only matches
+    /// profiles with that exact name. Otherwise, any unique part of the
+    /// path to profile can be used as an identifier e.g. `-p Brave` can be
+    /// resolved to `~/.config/BraveSoftware/Brave-Browser/Default`
     #[clap(short, long, default_value_t, value_parser)]
     pub profile: String,
 
-    /// List valid browser profiles for the --profile option
+    /// Read from a temporary copy of each cookie database, this makes it
+    /// possible to read cookies while the browser is running.
+    /// All profiles are read-only and the copies use additional disk space.
+    #[clap(long)]
+    snapshot: bool,
+
+    /// Open every profile read-only and disable all commands and keys that
+    /// modify cookies. Always enabled in builds with the `readonly` feature.
+    #[clap(long)]
+    readonly: bool,
+
+    /// Merge subdomains into a single entry for their site, e.g.
+    /// `www.example.co.uk` and `login.example.co.uk` are listed as
+    /// `example.co.uk` in the TUI and in the output of `domains`
+    #[clap(long)]
+    flatten: bool,
+
+    /// Show how many cookies expire within different time ranges
+    /// for each profile
+    #[clap(long)]
+    histogram: bool,
+
+    /// Show the number of cookies and domains in each profile together
+    /// with the size and last modification time of its database
+    #[clap(long)]
+    stats: bool,
+
+    /// Output --stats as JSON
+    #[clap(long, requires = "stats")]
+    json: bool,
+
+    /// Delete all cookies from the newline separated domains read from
+    /// stdin, e.g. `cat blocklist.txt | rokie --delete-stdin`.
+    /// Cookies for both `example.com` and `.example.com` are removed.
+    #[clap(long)]
+    delete_stdin: bool,
+
+    /// Delete the cookie with a `rowid` from the JSON output of `cookies`,
+    /// e.g. `--delete-rowid Default 42`. The profile is a path to a cookie
+    /// database or part of the path to a profile.
+    #[clap(long, number_of_values = 2, value_names = &["PROFILE", "ROWID"])]
+    delete_rowid: Vec<String>,
+
+    /// List valid browser profiles for the --profile option,
+    /// the search directory of each profile is shown with --debug
     #[clap(long, takes_value = false, value_parser)]
     pub list_profiles: bool,
 
+    /// Print the directories that are searched for browser profiles
+    /// and whether each of them exists
+    #[clap(long, takes_value = false, value_parser)]
+    pub print_search_dirs: bool,
+
+    /// Print the paths of the keymap, the pinned domains and the debug log
+    /// and whether each of them exists. The files are placed according to
+    /// the XDG base directory specification.
+    #[clap(long, takes_value = false, value_parser)]
+    pub print_paths: bool,
+
     /// Perform all commands on a supplied cookie database
     /// (overrides --profile)
     #[clap(long, short, default_value_t, value_parser)]
@@ -121,41 +498,258 @@ pub struct Args {
 }
 
 //=== Config ===//
+/// Ordering of domains and cookies in the TUI and of the output from
+/// `cookies` and `grep`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Domains in alphabetical order and cookies in database order
+    Name,
+    /// Most recently accessed domains and cookies first
+    LastAccess,
+}
+
+impl SortOrder {
+    /// Parse the value of a --sort option
+    fn from_name(name: &str) -> Self {
+        if name == "recent" {
+            SortOrder::LastAccess
+        } else {
+            SortOrder::Name
+        }
+    }
+}
+
+/// Ordering of the profiles in the TUI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileOrder {
+    /// Alphabetical order of the database paths
+    Name,
+    /// Most recently modified databases first
+    Modified,
+}
+
+/// Fields of a cookie that a search in the TUI is matched against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchScope {
+    Names,
+    Values,
+    Both,
+}
+
+impl SearchScope {
+    /// Split a scope prefix, e.g. `v:` from a query, queries without
+    /// a prefix use the `default` scope
+    pub fn from_query(query: &str, default: SearchScope) -> (Self, &str) {
+        for (prefix, scope) in [
+            ("n:", SearchScope::Names),
+            ("v:", SearchScope::Values),
+            ("b:", SearchScope::Both),
+        ] {
+            if let Some(rest) = query.strip_prefix(prefix) {
+                return (scope, rest);
+            }
+        }
+        (default, query)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SearchScope::Names => "names",
+            SearchScope::Values => "values",
+            SearchScope::Both => "both",
+        }
+    }
+}
+
+/// Errors that terminate the program, each kind maps to
+/// a distinct process exit code (see the README)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitError {
+    /// Invalid arguments or an unexpected failure
+    General,
+    /// No cookie database matched the search
+    NoProfiles,
+    /// The browser is running and holds a lock on its database
+    BrowserRunning,
+    /// A cookie database or input file could not be read
+    Read,
+    /// Output could not be written or a database could not be modified,
+    /// this includes read-only profiles that were skipped
+    Write,
+    /// Nothing would be removed with `--count-only --fail-if-empty`
+    NothingToRemove,
+    /// A cookie database is locked by a running browser
+    Locked,
+}
+
+impl ExitError {
+    pub fn code(&self) -> i32 {
+        match self {
+            ExitError::General => 1,
+            ExitError::NoProfiles => 2,
+            ExitError::BrowserRunning => 3,
+            ExitError::Read => 4,
+            ExitError::Write => 5,
+            ExitError::NothingToRemove => 6,
+            ExitError::Locked => 7,
+        }
+    }
+
+    /// Terminate the process with the exit code of the error
+    pub fn exit(self) -> ! {
+        std::process::exit(self.code())
+    }
+}
+
 #[derive(Debug)]
 pub struct Config {
-    pub err_exit: i32,
-
     pub debug: bool,
+    /// Path to the debug log if set through `DEBUG_LOG_ENV`
+    pub log_file: Option<String>,
     pub nocolor: bool,
+    pub quiet: bool,
+    pub dry_run: bool,
+    pub yes: bool,
+    pub ignore_running: bool,
+    pub count_only: bool,
+    pub audit_log: Option<String>,
+    pub fail_if_empty: bool,
+    pub follow_symlinks: bool,
+    pub deep_scan: bool,
+    pub profile_dir: Option<String>,
+    pub allow_insecure: bool,
+    /// Column names from --column-map, keyed by `browser.Field`
+    pub column_map: HashMap<String, String>,
+    pub snapshot: bool,
+    pub readonly: bool,
+    pub flatten: bool,
+    pub delete_stdin: bool,
+    /// The profile and rowid from --delete-rowid
+    pub delete_rowid: Option<(String, String)>,
+    pub histogram: bool,
+    pub stats: bool,
+    pub stats_json: bool,
 
     // Subcmd: cookies
     pub fields: String,
     pub no_heading: bool,
     pub list_fields: bool,
     pub domain: String,
+    pub format: ExportFormat,
+    pub list_formats: bool,
+    pub output: String,
+    pub force: bool,
+    pub anonymize: bool,
 
     // Subcmd: clean
     pub clean: bool,
     pub whitelist: String,
+    pub prune_older_than: Option<u32>,
     pub apply: bool,
 
+    // Subcmd: diff
+    pub diff: bool,
+    pub diff_profiles: Vec<String>,
+    pub diff_json: bool,
+
+    // Subcmd: audit
+    pub audit: bool,
+    pub audit_profile: Option<String>,
+    pub audit_json: bool,
+
+    // Subcmd: replace
+    pub replace: bool,
+    pub replace_domain: String,
+    pub replace_pattern: String,
+    pub replace_with: String,
+
+    // Subcmd: grep
+    pub grep: bool,
+    pub domains: bool,
+    pub grep_pattern: String,
+    pub grep_ignore_case: bool,
+    pub grep_values: bool,
+    /// Maximum age in seconds of the last access of a cookie, from --since
+    pub since: Option<i64>,
+    /// Maximum number of cookies to output from `cookies` and `grep`
+    pub limit: Option<usize>,
+
     // Subcmd: tui
     pub tui: bool,
+    pub sort: SortOrder,
+    pub profile_order: ProfileOrder,
+    pub search_scope: SearchScope,
+    pub profile_filter: Option<String>,
+    pub auto_select: bool,
+    pub watch: bool,
+    pub keymap: Option<String>,
+    pub osc52: bool,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
-            err_exit: 1,
             debug: false,
+            log_file: None,
             whitelist: String::from(""),
             no_heading: false,
             fields: String::from(""),
             list_fields: false,
             domain: String::from(""),
+            format: ExportFormat::Fields,
+            list_formats: false,
+            output: String::from(""),
+            force: false,
+            anonymize: false,
             nocolor: false,
+            quiet: false,
+            dry_run: false,
+            yes: false,
+            ignore_running: false,
+            count_only: false,
+            audit_log: None,
+            fail_if_empty: false,
+            follow_symlinks: false,
+            deep_scan: false,
+            profile_dir: None,
+            allow_insecure: false,
+            column_map: HashMap::new(),
+            snapshot: false,
+            readonly: false,
+            flatten: false,
+            delete_stdin: false,
+            delete_rowid: None,
+            histogram: false,
+            stats: false,
+            stats_json: false,
+            diff: false,
+            diff_profiles: vec![],
+            diff_json: false,
+            audit: false,
+            audit_profile: None,
+            audit_json: false,
+            replace: false,
+            replace_domain: String::from(""),
+            replace_pattern: String::from(""),
+            replace_with: String::from(""),
+            grep: false,
+            domains: false,
+            grep_pattern: String::from(""),
+            grep_ignore_case: false,
+            grep_values: false,
+            since: None,
+            limit: None,
             tui: false,
+            sort: SortOrder::Name,
+            profile_order: ProfileOrder::Name,
+            search_scope: SearchScope::Names,
+            profile_filter: None,
+            auto_select: true,
+            watch: false,
+            osc52: false,
+            keymap: None,
             clean: false,
+            prune_older_than: None,
             apply: false,
         }
     }
@@ -164,9 +758,34 @@ impl Default for Config {
 impl Config {
     /// Initialise a new config object from an Args struct
     pub fn from_args(args: &Args) -> Self {
-        let mut cfg = Config::default();
-        cfg.nocolor = args.nocolor;
-        cfg.debug = args.debug;
+        let mut cfg = Config {
+            nocolor: args.nocolor,
+            quiet: args.quiet,
+            debug: args.debug,
+            log_file: std::env::var(DEBUG_LOG_ENV).ok(),
+            dry_run: args.dry_run,
+            yes: args.yes,
+            ignore_running: args.ignore_running,
+            count_only: args.count_only,
+            audit_log: args.audit_log.clone(),
+            fail_if_empty: args.fail_if_empty,
+            follow_symlinks: args.follow_symlinks,
+            deep_scan: args.deep_scan,
+            profile_dir: args.profile_dir.clone(),
+            allow_insecure: args.allow_insecure,
+            snapshot: args.snapshot,
+            readonly: args.readonly || cfg!(feature = "readonly"),
+            flatten: args.flatten,
+            delete_stdin: args.delete_stdin,
+            delete_rowid: match &args.delete_rowid[..] {
+                [profile, rowid] => Some((profile.clone(), rowid.clone())),
+                _ => None,
+            },
+            histogram: args.histogram,
+            stats: args.stats,
+            stats_json: args.json,
+            ..Default::default()
+        };
 
         match &args.subargs {
             Some(SubArgs::Cookies {
@@ -174,26 +793,129 @@ impl Config {
                 list_fields,
                 fields,
                 domain,
+                format,
+                list_formats,
+                output,
+                force,
+                anonymize,
+                since,
+                sort,
+                limit,
             }) => {
+                cfg.since = *since;
+                cfg.sort = SortOrder::from_name(sort);
+                cfg.limit = *limit;
                 cfg.no_heading = *no_heading;
                 cfg.list_fields = *list_fields;
                 cfg.domain = domain.clone();
                 cfg.fields = fields.clone();
+                cfg.format = ExportFormat::from_name(format)
+                    .expect("Invalid --format value");
+                cfg.list_formats = *list_formats;
+                cfg.output = output.clone();
+                cfg.force = *force;
+                cfg.anonymize = *anonymize;
                 cfg
             }
-            Some(SubArgs::Clean { whitelist, apply }) => {
+            Some(SubArgs::Clean {
+                whitelist,
+                prune_older_than,
+                apply,
+            }) => {
                 cfg.clean = true;
                 cfg.apply = *apply;
+                cfg.prune_older_than = *prune_older_than;
                 cfg.whitelist = whitelist.clone();
                 cfg
             }
-            Some(SubArgs::Tui {}) => {
+            Some(SubArgs::Replace {
+                domain,
+                pattern,
+                replacement,
+            }) => {
+                cfg.replace = true;
+                cfg.replace_domain = domain.clone();
+                cfg.replace_pattern = pattern.clone();
+                cfg.replace_with = replacement.clone();
+                cfg
+            }
+            Some(SubArgs::Diff {
+                profile_a,
+                profile_b,
+                json,
+            }) => {
+                cfg.diff = true;
+                cfg.diff_profiles = vec![profile_a.clone(), profile_b.clone()];
+                cfg.diff_json = *json;
+                cfg
+            }
+            Some(SubArgs::Audit { profile, json }) => {
+                cfg.audit = true;
+                cfg.audit_profile = profile.clone();
+                cfg.audit_json = *json;
+                cfg
+            }
+            Some(SubArgs::Grep {
+                pattern,
+                ignore_case,
+                values,
+                since,
+                sort,
+                limit,
+            }) => {
+                cfg.since = *since;
+                cfg.sort = SortOrder::from_name(sort);
+                cfg.limit = *limit;
+                cfg.grep = true;
+                cfg.grep_pattern = pattern.clone();
+                cfg.grep_ignore_case = *ignore_case;
+                cfg.grep_values = *values;
+                cfg
+            }
+            Some(SubArgs::Domains) => {
+                cfg.domains = true;
+                cfg
+            }
+            Some(SubArgs::Tui {
+                sort,
+                profile_order,
+                search_scope,
+                filter,
+                no_auto_select,
+                watch,
+                keymap,
+                osc52,
+            }) => {
                 cfg.tui = true;
+                cfg.watch = *watch;
+                cfg.auto_select = !*no_auto_select;
+                cfg.osc52 = *osc52;
+                cfg.profile_filter = filter.clone();
+                cfg.keymap = keymap.clone();
+                cfg.sort = SortOrder::from_name(sort);
+                cfg.profile_order = if profile_order == "modified" {
+                    ProfileOrder::Modified
+                } else {
+                    ProfileOrder::Name
+                };
+                cfg.search_scope = match search_scope.as_str() {
+                    "values" => SearchScope::Values,
+                    "both" => SearchScope::Both,
+                    _ => SearchScope::Names,
+                };
                 cfg
             }
             None => cfg,
         }
     }
+    /// Path to the debug log, `DEBUG_LOG` unless overridden
+    pub fn log_path(&self) -> PathBuf {
+        match &self.log_file {
+            Some(path) => PathBuf::from(path),
+            None => app_dir(STATE_DIR).join(DEBUG_LOG),
+        }
+    }
+
     /// Used to access the global config object in the program
     pub fn global() -> &'static Self {
         CONFIG
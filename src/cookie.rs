@@ -1,11 +1,15 @@
 use std::fmt;
 
 use chrono::{DateTime, TimeZone, Utc};
+use serde::Serialize;
 
-use crate::config::ENCRYPTED_VALUE;
-use crate::{ALL_FIELDS, COOKIE_FIELDS};
+use crate::config::{
+    ALL_FIELDS, COOKIE_FIELDS, COOKIE_PREFIXES, ENCRYPTED_VALUE,
+    INVALID_TIMESTAMP,
+};
+use crate::util::site;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Cookie {
     /// The domain that created the cookie
     pub host: String,
@@ -40,13 +44,66 @@ pub struct Cookie {
     ///     Strict == 2
     ///     Lax == 1
     ///     None == 0
+    /// Chrome uses -1 when the attribute is unspecified, which is treated
+    /// as `None`.
     pub samesite: i32,
 
     /// The encrypted value of a cooke, unique to Chrome
+    #[serde(skip)]
     pub encrypted_value: Vec<u8>,
+
+    /// Firefox attributes that separate cookies from the same host, e.g.
+    /// `^userContextId=2` for a cookie inside of a container.
+    /// Always empty for Chrome.
+    pub origin_attributes: String,
+
+    /// The `Priority` attribute of a Chrome cookie: `Low`, `Medium` or
+    /// `High`. This and the `source_*` fields are `None` for Firefox
+    /// and for Chrome databases with an older schema.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// The scheme of the origin that set the cookie (Chrome)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_scheme: Option<i32>,
+    /// The port of the origin that set the cookie (Chrome)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_port: Option<i32>,
+    /// Chrome tracks if a cookie outlives the session separately from
+    /// its expiry, `None` if the column does not exist
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persistent: Option<bool>,
+    /// The site (eTLD+1) that Firefox groups the cookie under, `None`
+    /// for Chrome and for Firefox databases without `baseDomain`
+    #[serde(skip)]
+    pub base_domain: Option<String>,
+
+    /// The SQLite `rowid` of the cookie, used to modify a single cookie
+    /// even if several rows share the same host, name and path.
+    /// Included in JSON output for `--delete-rowid`.
+    pub rowid: i64,
 }
 
 impl Cookie {
+    /// Cookies that are removed at the end of the browser session,
+    /// determined from the expiry if the database lacks `is_persistent`
+    pub fn is_session(&self) -> bool {
+        self.persistent.map_or(self.expiry == 0, |p| !p)
+    }
+
+    /// The site of the host, taken from Firefox's `baseDomain` if it
+    /// was read, it is otherwise derived from the public suffix list
+    pub fn site(&self) -> &str {
+        match self.base_domain.as_deref() {
+            Some(base_domain) if !base_domain.is_empty() => base_domain,
+            _ => site(&self.host),
+        }
+    }
+
+    /// Persistent cookies with an expiry before `now` (UNIX epoch time)
+    pub fn is_expired(&self, now: i64) -> bool {
+        !self.is_session() && self.expiry < now
+    }
+
     /// Construct a newline separated string with the specified field names
     /// The `fields` parameter is a comma separated string or `All`
     pub fn fields_as_str(
@@ -62,10 +119,10 @@ impl Cookie {
                 if !fields.split(",").any(|s| s == *f || fields == ALL_FIELDS) {
                     String::from("")
                 } else {
-                    self.match_field(*f, use_name, color)
+                    self.match_field(f, use_name, color)
                 }
             })
-            .filter(|f| f != "")
+            .filter(|f| !f.is_empty())
             .collect();
         values.sort();
         values.join("\n")
@@ -114,31 +171,133 @@ impl Cookie {
                 color,
                 use_name,
                 "LastAccess",
-                Self::date_fmt(self.last_access)
+                Self::date_fmt(self.last_access),
             ),
             "HttpOnly" => {
                 self.field_fmt(color, use_name, "HttpOnly", self.http_only)
             }
             "Secure" => self.field_fmt(color, use_name, "Secure", self.secure),
             "SameSite" => {
-                let samesite = match self.samesite {
-                    2 => "Strict",
-                    1 => "Lax",
-                    -1 | 0 => "None",
-                    _ => panic!("Unknown SameSite type"),
-                };
-                self.field_fmt(color, use_name, "SameSite", samesite)
+                self.field_fmt(color, use_name, "SameSite", self.samesite_str())
             }
             _ => panic!("Unknown cookie field"),
         }
     }
 
-    fn date_fmt(epoch: i64) -> DateTime<Utc> {
+    /// The name of the `SameSite` value of the cookie
+    pub fn samesite_str(&self) -> &'static str {
+        match self.samesite {
+            2 => "Strict",
+            1 => "Lax",
+            -1 | 0 => "None",
+            _ => panic!("Unknown SameSite type"),
+        }
+    }
+
+    /// The name of the `Priority` value of the cookie (if any)
+    pub fn priority_str(&self) -> Option<&'static str> {
+        self.priority.map(|p| match p {
+            0 => "Low",
+            2 => "High",
+            _ => "Medium",
+        })
+    }
+
+    /// The name of the source scheme of the cookie (if any)
+    pub fn source_scheme_str(&self) -> Option<&'static str> {
+        self.source_scheme.map(|s| match s {
+            1 => "NonSecure",
+            2 => "Secure",
+            _ => "Unset",
+        })
+    }
+
+    /// The `userContextId` of the Firefox container that the
+    /// cookie belongs to, `None` outside of a container
+    pub fn container_id(&self) -> Option<u32> {
+        self.origin_attributes
+            .trim_start_matches('^')
+            .split('&')
+            .find_map(|attr| attr.strip_prefix("userContextId="))
+            .and_then(|id| id.parse().ok())
+            .filter(|id| *id != 0)
+    }
+
+    /// Check if the cookie is from `domain` and has the given `name`
+    /// and `origin_attributes`, an empty name or `None` matches any value
+    pub fn matches_scope(
+        &self,
+        domain: &str,
+        name: &str,
+        origin_attributes: Option<&str>,
+    ) -> bool {
+        self.host == domain
+            && (name.is_empty() || self.name == name)
+            && origin_attributes.is_none_or(|a| self.origin_attributes == a)
+    }
+
+    /// Check if the host or name of the cookie contains `pattern`, the
+    /// value is also checked if `values` is set. With `ignore_case`,
+    /// the `pattern` is expected to be lowercase.
+    pub fn contains(
+        &self,
+        pattern: &str,
+        ignore_case: bool,
+        values: bool,
+    ) -> bool {
+        let mut fields = vec![&self.host, &self.name];
+        if values {
+            fields.push(&self.value);
+        }
+        fields.iter().any(|f| {
+            if ignore_case {
+                f.to_lowercase().contains(pattern)
+            } else {
+                f.contains(pattern)
+            }
+        })
+    }
+
+    /// The security prefix of the cookie name (if any)
+    pub fn name_prefix(&self) -> Option<&'static str> {
+        COOKIE_PREFIXES
+            .iter()
+            .find(|p| self.name.starts_with(*p))
+            .copied()
+    }
+
+    /// Requirements of the name prefix that the cookie does not satisfy.
+    /// Both prefixes require the `Secure` attribute, `__Host-` cookies must
+    /// also be host-only (no `Domain` attribute) with the path set to `/`.
+    /// https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Set-Cookie#cookie_prefixes
+    pub fn prefix_violations(&self) -> Vec<&'static str> {
+        let mut violations = vec![];
+        if let Some(prefix) = self.name_prefix() {
+            if !self.secure {
+                violations.push("Secure not set");
+            }
+            if prefix == "__Host-" {
+                if self.path != "/" {
+                    violations.push("Path is not /");
+                }
+                if self.host.starts_with('.') {
+                    violations.push("Domain is set");
+                }
+            }
+        }
+        violations
+    }
+
+    fn date_fmt(epoch: i64) -> String {
+        if epoch == INVALID_TIMESTAMP {
+            return "invalid".to_string();
+        }
         match Utc.timestamp_opt(epoch, 0) {
             chrono::offset::LocalResult::Single(s) => s,
             chrono::offset::LocalResult::Ambiguous(e, _) => e,
-            _ => DateTime::from_timestamp(0, 0).unwrap()
+            _ => DateTime::from_timestamp(0, 0).unwrap(),
         }
+        .to_string()
     }
 
     /// The output format of cookie fields listed with the `cookies` option
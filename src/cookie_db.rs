@@ -1,9 +1,19 @@
 use std::cmp;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::hash::{Hash, Hasher};
+use std::time::UNIX_EPOCH;
 
-use crate::config::{Config, COOKIE_FIELDS};
+use crate::config::{
+    Config, SortOrder, CHROME_EPOCH_OFFSET, CHROME_OPTIONAL_FIELDS,
+    COOKIE_FIELDS, FIREFOX_CONTAINERS, INVALID_TIMESTAMP, MAX_TIMESTAMP,
+};
 use crate::cookie::Cookie;
-use crate::util::{get_home, DbType};
+use crate::error::DbError;
+use crate::search::SearchIndex;
+use crate::util::{
+    get_home, is_writable, open_for_reading, site, write_audit_log, AuditEntry,
+    DbType,
+};
 use crate::{debugln, msg_prefix};
 
 #[derive(Debug)]
@@ -11,6 +21,25 @@ pub struct CookieDB {
     pub path: std::path::PathBuf,
     pub typing: DbType,
     pub cookies: Vec<Cookie>,
+    /// Set if the current user lacks write access to the database,
+    /// destructive operations are disabled for read-only databases
+    pub readonly: bool,
+    /// Number of cookies in the database, fetched with a `COUNT(*)` query
+    /// on creation to avoid loading all cookies
+    pub cookie_count: usize,
+    /// Names of the Firefox containers in the profile, keyed by the
+    /// `userContextId`, loaded together with the cookies
+    pub containers: HashMap<u32, String>,
+    /// The entry in `SEARCH_DIRS` where the database was found,
+    /// `None` for a database passed with --file
+    pub search_dir: Option<&'static str>,
+    /// Last modification of the database file in UNIX epoch time
+    /// (0 if unknown), read on creation
+    pub modified: i64,
+    /// Search indices of the hosts and names of the loaded cookies,
+    /// rebuilt whenever cookies are loaded or removed
+    pub domain_index: SearchIndex,
+    pub name_index: SearchIndex,
 }
 
 //== Enable hashing ==//
@@ -31,17 +60,66 @@ impl Hash for CookieDB {
 //== Enable sorting ==//
 impl PartialOrd for CookieDB {
     fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
-        Some(self.path.cmp(&other.path))
+        Some(self.cmp(other))
     }
 }
 impl Ord for CookieDB {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
-        self.partial_cmp(&other).unwrap()
+        self.path.cmp(&other.path)
     }
 }
 
 //== Main impl ==//
 impl CookieDB {
+    /// Create a new database object without any loaded cookies
+    pub fn new(path: std::path::PathBuf, typing: DbType) -> Self {
+        // Snapshots are never written to
+        let readonly = Config::global().readonly
+            || Config::global().snapshot
+            || !is_writable(&path);
+        let modified = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs() as i64);
+        let mut cdb = CookieDB {
+            path,
+            typing,
+            cookies: vec![],
+            readonly,
+            cookie_count: 0,
+            containers: HashMap::new(),
+            search_dir: None,
+            modified,
+            domain_index: SearchIndex::default(),
+            name_index: SearchIndex::default(),
+        };
+        cdb.cookie_count = cdb.count_cookies().unwrap_or(0);
+        cdb
+    }
+
+    /// Name of the profile directory of the database, e.g. `Default` or
+    /// `Profile 1` for Chrome. Newer versions of Chrome store cookies
+    /// in a `Network` subdirectory of the profile.
+    pub fn profile_name(&self) -> Option<String> {
+        let mut dir = self.path.parent()?;
+        if dir.file_name()? == "Network" {
+            dir = dir.parent()?;
+        }
+        Some(dir.file_name()?.to_string_lossy().to_string())
+    }
+
+    /// Check if the database belongs to the profile given with --profile,
+    /// i.e. if `identifier` is the name of its profile directory or, if
+    /// `exact` is not set, any part of its path
+    pub fn matches_profile(&self, identifier: &str, exact: bool) -> bool {
+        if exact {
+            self.profile_name().as_deref() == Some(identifier)
+        } else {
+            self.path.to_string_lossy().contains(identifier)
+        }
+    }
+
     /// Return the parent of the current path and replaces $HOME with "~".
     /// Returns `path` as is if it is not an absolute path.
     pub fn path_short(&self) -> String {
@@ -71,39 +149,300 @@ impl CookieDB {
     ///
     /// Cookies with a Session-only lifetime will have 0 as their
     /// expiry date in Chrome
+    ///
+    /// Timestamps before the UNIX epoch or after `MAX_TIMESTAMP` (which
+    /// only occur in corrupt databases) are mapped to `INVALID_TIMESTAMP`
     fn get_unix_epoch(&self, timestamp: i64) -> i64 {
         if timestamp == 0 {
+            return 0;
+        }
+        let epoch = if self.typing == DbType::Firefox {
+            Some(timestamp / 1_000_000)
+        } else {
+            (timestamp / 1_000_000).checked_sub(CHROME_EPOCH_OFFSET)
+        };
+        match epoch {
+            Some(e) if (0..=MAX_TIMESTAMP).contains(&e) => e,
+            _ => INVALID_TIMESTAMP,
+        }
+    }
+
+    /// Inverse of `get_unix_epoch()`, converts a UNIX epoch timestamp
+    /// into the timestamp format used by the browser
+    fn get_native_timestamp(&self, epoch: i64) -> i64 {
+        if epoch == 0 {
             0
         } else if self.typing == DbType::Firefox {
-            timestamp / 1_000_000
+            epoch.saturating_mul(1_000_000)
         } else {
-            (timestamp / 1_000_000) - 11_644_473_600
+            epoch
+                .saturating_add(CHROME_EPOCH_OFFSET)
+                .saturating_mul(1_000_000)
+        }
+    }
+
+    /// Execute a statement that modifies the database, nothing is written
+    /// if `--dry-run` was passed. The modification is recorded in the
+    /// --audit-log (if any) and rolled back if the log cannot be written.
+    /// Returns the number of modified rows.
+    fn execute<P: rusqlite::Params>(
+        &self,
+        query: &str,
+        params: P,
+        audit: AuditEntry,
+    ) -> Result<usize, DbError> {
+        if Config::global().dry_run {
+            return Ok(0);
+        }
+        self.execute_logged(
+            query,
+            params,
+            audit,
+            Config::global().audit_log.as_deref(),
+        )
+    }
+
+    /// Execute a statement in a transaction that is only committed once
+    /// the entry has been written to `audit_log`
+    fn execute_logged<P: rusqlite::Params>(
+        &self,
+        query: &str,
+        params: P,
+        audit: AuditEntry,
+        audit_log: Option<&str>,
+    ) -> Result<usize, DbError> {
+        let mut conn = rusqlite::Connection::open(&self.path)?;
+        let tx = conn.transaction()?;
+        let count = tx.execute(query, params)?;
+        if let Some(audit_log) = audit_log {
+            // Dropping the transaction rolls it back
+            write_audit_log(audit_log, &self.path, &audit)
+                .map_err(|e| DbError::AuditLog(audit_log.to_string(), e))?;
+        }
+        tx.commit()?;
+        conn.close().unwrap();
+        Ok(count)
+    }
+
+    /// Name of the column for a field from `COOKIE_FIELDS`, the default
+    /// name is replaced if the field is mapped in --column-map
+    fn column(&self, field: &str) -> &'static str {
+        let (browser, idx) = if self.typing == DbType::Chrome {
+            ("chrome", 0)
+        } else {
+            ("firefox", 1)
+        };
+        Config::global()
+            .column_map
+            .get(&format!("{browser}.{field}"))
+            .map(|c| c.as_str())
+            .unwrap_or(COOKIE_FIELDS[field][idx])
+    }
+
+    /// Count the cookies in the database without loading them
+    pub fn count_cookies(&self) -> Result<usize, rusqlite::Error> {
+        self.count_where("1", [])
+    }
+
+    /// Count the distinct hosts in the database without loading any cookies
+    pub fn count_domains(&self) -> Result<usize, rusqlite::Error> {
+        let (conn, _snapshot) = open_for_reading(&self.path)?;
+        let query = format!(
+            "SELECT COUNT(DISTINCT {}) FROM {};",
+            self.column("Host"),
+            self.table_name()
+        );
+        let count = conn.query_row(&query, [], |row| row.get::<_, usize>(0))?;
+        conn.close().unwrap();
+        Ok(count)
+    }
+
+    /// Count the cookies that match an SQL condition without loading them
+    fn count_where<P: rusqlite::Params>(
+        &self,
+        condition: &str,
+        params: P,
+    ) -> Result<usize, rusqlite::Error> {
+        let (conn, _snapshot) = open_for_reading(&self.path)?;
+        let query = format!(
+            "SELECT COUNT(*) FROM {} WHERE {};",
+            self.table_name(),
+            condition
+        );
+        let count =
+            conn.query_row(&query, params, |row| row.get::<_, usize>(0))?;
+        conn.close().unwrap();
+        Ok(count)
+    }
+
+    /// Count the cookies from each of the `domains`, both with and
+    /// without a leading dot, i.e. the cookies removed by `--delete-stdin`
+    pub fn count_for_domains(
+        &self,
+        domains: &[String],
+    ) -> Result<usize, rusqlite::Error> {
+        let hosts: Vec<String> = domains
+            .iter()
+            .map(|d| d.trim_start_matches('.'))
+            .flat_map(|d| [d.to_string(), format!(".{d}")])
+            .collect();
+        if hosts.is_empty() {
+            return Ok(0);
         }
+        let placeholders = vec!["?"; hosts.len()].join(",");
+        let condition =
+            format!("{} IN ({})", self.column("Host"), placeholders);
+        self.count_where(&condition, rusqlite::params_from_iter(hosts))
+    }
+
+    /// Count the cookies that `clean()` would remove
+    pub fn count_clean(
+        &self,
+        whitelist: &[String],
+        older_than: Option<i64>,
+    ) -> Result<usize, rusqlite::Error> {
+        self.count_where(&self.clean_condition(whitelist, older_than), [])
+    }
+
+    /// The condition that selects the cookies removed by `clean()`
+    fn clean_condition(
+        &self,
+        whitelist: &[String],
+        older_than: Option<i64>,
+    ) -> String {
+        let mut condition =
+            format!("{} NOT IN ({})", self.column("Host"), whitelist.join(","));
+        if let Some(epoch) = older_than {
+            condition += &format!(
+                " AND {} < {}",
+                self.column("LastAccess"),
+                self.get_native_timestamp(epoch)
+            );
+        }
+        condition
+    }
+
+    /// The names of the columns in the cookies table
+    fn columns(
+        &self,
+        conn: &rusqlite::Connection,
+    ) -> Result<Vec<String>, rusqlite::Error> {
+        let mut stmt = conn
+            .prepare(&format!("PRAGMA table_info({});", self.table_name()))?;
+        let columns = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .filter_map(|c| c.ok())
+            .collect();
+        Ok(columns)
+    }
+
+    /// The distinct hosts in the database in sorted order, read without
+    /// loading any cookies
+    pub fn domains(&self) -> Result<Vec<String>, rusqlite::Error> {
+        let (conn, _snapshot) = open_for_reading(&self.path)?;
+        let query = format!(
+            "SELECT DISTINCT {host} FROM {} ORDER BY {host};",
+            self.table_name(),
+            host = self.column("Host"),
+        );
+        let mut stmt = conn.prepare(&query)?;
+        let domains =
+            stmt.query_map([], |row| row.get::<_, String>(0))?.collect();
+        domains
+    }
+
+    /// The distinct sites of the hosts in the database in sorted order,
+    /// taken from `baseDomain` for Firefox databases with the column
+    pub fn sites(&self) -> Result<Vec<String>, rusqlite::Error> {
+        let (conn, _snapshot) = open_for_reading(&self.path)?;
+        let base_domain = if self.typing == DbType::Firefox
+            && self.columns(&conn)?.iter().any(|c| c == "baseDomain")
+        {
+            "baseDomain"
+        } else {
+            "NULL"
+        };
+        let query = format!(
+            "SELECT DISTINCT {},{} FROM {};",
+            self.column("Host"),
+            base_domain,
+            self.table_name(),
+        );
+        let mut stmt = conn.prepare(&query)?;
+        let sites: BTreeSet<String> = stmt
+            .query_map([], |row| {
+                let host = row.get::<_, String>(0)?;
+                Ok(match row.get::<_, Option<String>>(1)? {
+                    Some(base_domain) if !base_domain.is_empty() => base_domain,
+                    _ => site(&host).to_string(),
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(sites.into_iter().collect())
     }
 
     /// Load all cookies from the current `path` into the `cookies` vector
     pub fn load_cookies(&mut self) -> Result<(), rusqlite::Error> {
-        let conn = rusqlite::Connection::open(&self.path)?;
-        let field_idx = if self.typing == DbType::Chrome { 0 } else { 1 };
+        let (conn, _snapshot) = open_for_reading(&self.path)?;
         let encrypted_field = if self.typing == DbType::Chrome {
             "encrypted_value"
         } else {
             "NULL"
         };
+        // Firefox stores the container of a cookie in `originAttributes`
+        let origin_field = if self.typing == DbType::Firefox {
+            "originAttributes"
+        } else {
+            "''"
+        };
+        // Rows would be silently skipped if a column was missing
+        let columns = self.columns(&conn)?;
+        for field in COOKIE_FIELDS.keys() {
+            let column = self.column(field);
+            if !columns.iter().any(|c| c == column) {
+                return Err(rusqlite::Error::InvalidColumnName(
+                    column.to_string(),
+                ));
+            }
+        }
+        // Firefox groups cookies by site in `baseDomain` since version 69
+        let base_domain_field = if self.typing == DbType::Firefox
+            && columns.iter().any(|c| c == "baseDomain")
+        {
+            "baseDomain"
+        } else {
+            "NULL"
+        };
+        // Columns that only exist in newer Chrome schemas
+        let optional_fields: Vec<&str> = CHROME_OPTIONAL_FIELDS
+            .iter()
+            .map(|f| {
+                if self.typing == DbType::Chrome
+                    && columns.iter().any(|c| c == f)
+                {
+                    *f
+                } else {
+                    "NULL"
+                }
+            })
+            .collect();
 
         let query = format!(
-            "SELECT {},{},{},{},{},{},{},{},{},{},{} FROM {};",
-            COOKIE_FIELDS["Host"][field_idx],
-            COOKIE_FIELDS["Name"][field_idx],
-            COOKIE_FIELDS["Value"][field_idx],
-            COOKIE_FIELDS["Path"][field_idx],
-            COOKIE_FIELDS["Creation"][field_idx],
-            COOKIE_FIELDS["Expiry"][field_idx],
-            COOKIE_FIELDS["LastAccess"][field_idx],
-            COOKIE_FIELDS["HttpOnly"][field_idx],
-            COOKIE_FIELDS["Secure"][field_idx],
-            COOKIE_FIELDS["SameSite"][field_idx],
+            "SELECT {},{},{},{},{},{},{},{},{},{},{},{},{},{},rowid FROM {};",
+            self.column("Host"),
+            self.column("Name"),
+            self.column("Value"),
+            self.column("Path"),
+            self.column("Creation"),
+            self.column("Expiry"),
+            self.column("LastAccess"),
+            self.column("HttpOnly"),
+            self.column("Secure"),
+            self.column("SameSite"),
             encrypted_field,
+            origin_field,
+            optional_fields.join(","),
+            base_domain_field,
             self.table_name()
         );
         let mut stmt = conn.prepare(&query)?;
@@ -125,6 +464,13 @@ impl CookieDB {
                 secure: row.get::<_, bool>(8).unwrap(),
                 samesite: row.get::<_, i32>(9).unwrap(),
                 encrypted_value: row.get::<_, Vec<u8>>(10).unwrap_or(vec![]),
+                origin_attributes: row.get::<_, String>(11).unwrap(),
+                priority: row.get::<_, Option<i32>>(12).unwrap(),
+                source_scheme: row.get::<_, Option<i32>>(13).unwrap(),
+                source_port: row.get::<_, Option<i32>>(14).unwrap(),
+                persistent: row.get::<_, Option<bool>>(15).unwrap(),
+                base_domain: row.get::<_, Option<String>>(16).unwrap(),
+                rowid: row.get::<_, i64>(17).unwrap(),
             })
         })?;
 
@@ -132,8 +478,13 @@ impl CookieDB {
         // of results, Ok(), which we need to unwrap
         // before calling collect
         self.cookies = results_iter.filter_map(|r| r.ok()).collect();
+        self.cookie_count = self.cookies.len();
+        self.rebuild_index();
 
         if self.typing == DbType::Chrome { /* TODO: decrypt() */ }
+        if self.typing == DbType::Firefox {
+            self.load_containers();
+        }
 
         stmt.finalize().unwrap();
         conn.close().unwrap();
@@ -141,122 +492,425 @@ impl CookieDB {
     }
 
     /// Remove all cookies from the underlying database except those
-    /// from a domain within the whitelist
+    /// from a domain within the whitelist. If `older_than` is provided,
+    /// only cookies last accessed before the given UNIX epoch timestamp
+    /// are removed. Returns the number of (matching) cookies.
     pub fn clean(
         &self,
-        whitelist: &Vec<String>,
+        whitelist: &[String],
+        older_than: Option<i64>,
         apply: bool,
-    ) -> Result<(), rusqlite::Error> {
-        let field_idx = if self.typing == DbType::Chrome { 0 } else { 1 };
-
-        let query = format!(
-            "DELETE FROM {} WHERE {} NOT IN ({});",
-            self.table_name(),
-            COOKIE_FIELDS["Host"][field_idx],
-            whitelist.join(",")
-        );
+    ) -> Result<usize, DbError> {
+        let condition = self.clean_condition(whitelist, older_than);
+        let query =
+            format!("DELETE FROM {} WHERE {};", self.table_name(), condition);
 
         if apply {
-            let conn = rusqlite::Connection::open(&self.path)?;
             debugln!("{}", query);
-            conn.execute(&query, rusqlite::params![])?;
-            conn.close().unwrap();
+            let audit = AuditEntry {
+                action: "clean",
+                host: "*",
+                name: "*",
+            };
+            self.execute(&query, rusqlite::params![], audit)
         } else {
             println!("!> {query}");
+            Ok(self.count_where(&condition, [])?)
         }
-        Ok(())
     }
 
     /// Delete a cookie with a specific name from a domain or
     /// ALL cookies from a domain if no name is specified.
+    /// If `origin_attributes` is given, only cookies from the matching
+    /// Firefox container are removed, otherwise all containers are included.
     /// This call updates both the SQLite store and the
     /// internal `cookies` vector.
     pub fn delete_from_domain(
         &mut self,
         domain: &str,
         name: &str,
-    ) -> Result<(), rusqlite::Error> {
-        let field_idx = if self.typing == DbType::Chrome { 0 } else { 1 };
-
-        let query = if name.is_empty() {
-            format!(
-                "DELETE FROM {} WHERE {} == \"{}\";",
-                self.table_name(),
-                COOKIE_FIELDS["Host"][field_idx],
-                domain
-            )
-        } else {
-            format!(
-                "DELETE FROM {} WHERE {} == \"{}\" AND {} == \"{}\";",
-                self.table_name(),
-                COOKIE_FIELDS["Host"][field_idx],
-                domain,
-                COOKIE_FIELDS["Name"][field_idx],
-                name
-            )
-        };
+        origin_attributes: Option<&str>,
+    ) -> Result<(), DbError> {
+        let mut query = format!(
+            "DELETE FROM {} WHERE {} == ?1",
+            self.table_name(),
+            self.column("Host"),
+        );
+        let mut params: Vec<&dyn rusqlite::ToSql> = vec![&domain];
+        if !name.is_empty() {
+            params.push(&name);
+            query +=
+                &format!(" AND {} == ?{}", self.column("Name"), params.len());
+        }
+        // Only Firefox databases have an `originAttributes` column
+        let origin_param =
+            origin_attributes.filter(|_| self.typing == DbType::Firefox);
+        if let Some(attrs) = &origin_param {
+            params.push(attrs);
+            query += &format!(" AND originAttributes == ?{}", params.len());
+        }
+        query += ";";
 
         // Remove from backing store
-        let conn = rusqlite::Connection::open(&self.path)?;
-        conn.execute(&query, rusqlite::params![])?;
-        conn.close().unwrap();
+        let audit = AuditEntry {
+            action: "delete",
+            host: domain,
+            name: if name.is_empty() { "*" } else { name },
+        };
+        self.execute(&query, rusqlite::params_from_iter(params), audit)?;
 
-        if name.is_empty() {
-            // Retain all except cookies from the specified domain
-            self.cookies.retain(|c| c.host != domain)
-        } else {
-            // Retain all cookies except those that match `name`
-            // and are present on the specified domain
-            self.cookies.retain(|c| {
-                if c.host == domain {
-                    c.name != name
-                } else {
-                    true
-                }
-            })
-        }
+        // Retain all cookies except those that match the query
+        self.cookies
+            .retain(|c| !c.matches_scope(domain, name, origin_attributes));
+        self.cookie_count = self.cookies.len();
+        self.rebuild_index();
 
         Ok(())
     }
 
-    /// Deduplicated list of domains stored in the database
-    /// Returns a new `String` to dodge BC
-    pub fn domains(&self) -> Vec<String> {
-        let mut hst_names: Vec<String> =
-            self.cookies.iter().map(|c| c.host.to_owned()).collect();
-        hst_names.sort();
-        hst_names.dedup();
-        hst_names
+    /// Delete the cookie with the given `rowid`.
+    /// This call updates both the SQLite store and the
+    /// internal `cookies` vector.
+    pub fn delete_cookie(&mut self, rowid: i64) -> Result<(), DbError> {
+        let query =
+            format!("DELETE FROM {} WHERE rowid == ?1;", self.table_name());
+        let cookie = self.cookie_by_rowid(rowid);
+        let audit = AuditEntry {
+            action: "delete",
+            host: cookie.map_or("", |c| &c.host),
+            name: cookie.map_or("", |c| &c.name),
+        };
+        self.execute(&query, [rowid], audit)?;
+
+        self.cookies.retain(|c| c.rowid != rowid);
+        self.cookie_count = self.cookies.len();
+        self.rebuild_index();
+
+        Ok(())
     }
 
-    /// List of cookies for a specific domain
-    /// To return a non-reference list of cookies requires that
-    /// `Cookie` implements the copy trait
-    pub fn cookies_for_domain(&self, domain: &String) -> Vec<Cookie> {
+    /// Set the expiry of the cookie with the given `rowid` to a timestamp
+    /// in the past, see `expire_from_domain()`.
+    /// This call updates both the SQLite store and the
+    /// internal `cookies` vector.
+    pub fn expire_cookie(&mut self, rowid: i64) -> Result<(), DbError> {
+        let expired = 1;
+        let query = format!(
+            "UPDATE {} SET {} = ?1 WHERE rowid == ?2;",
+            self.table_name(),
+            self.column("Expiry"),
+        );
+        let cookie = self.cookie_by_rowid(rowid);
+        let audit = AuditEntry {
+            action: "expire",
+            host: cookie.map_or("", |c| &c.host),
+            name: cookie.map_or("", |c| &c.name),
+        };
+        let params = [self.get_native_timestamp(expired), rowid];
+        self.execute(&query, params, audit)?;
+
         self.cookies
-            .iter()
-            .filter(|c| c.host == *domain)
-            .map(|c| c.to_owned())
-            .collect()
+            .iter_mut()
+            .filter(|c| c.rowid == rowid)
+            .for_each(|c| c.expiry = expired);
+
+        Ok(())
     }
 
-    /// Return a cookie with a specific name from a specific domain
-    pub fn cookie_for_domain(
-        &self,
-        name: &String,
-        domain: &String,
-    ) -> Option<&Cookie> {
+    /// Set the value of the cookie with the given `rowid`.
+    /// This call updates both the SQLite store and the
+    /// internal `cookies` vector.
+    pub fn set_value(
+        &mut self,
+        rowid: i64,
+        value: &str,
+    ) -> Result<(), DbError> {
+        let query = format!(
+            "UPDATE {} SET {} = ?1 WHERE rowid == ?2;",
+            self.table_name(),
+            self.column("Value"),
+        );
+        let cookie = self.cookie_by_rowid(rowid);
+        let audit = AuditEntry {
+            action: "replace",
+            host: cookie.map_or("", |c| &c.host),
+            name: cookie.map_or("", |c| &c.name),
+        };
+        self.execute(&query, rusqlite::params![value, rowid], audit)?;
+
         self.cookies
-            .iter()
-            .find(|c| c.host == *domain && c.name == *name)
+            .iter_mut()
+            .filter(|c| c.rowid == rowid)
+            .for_each(|c| c.value = value.to_string());
+
+        Ok(())
     }
+
+    /// Delete all cookies except those with a host that contains `domain`
+    /// (without a leading dot), i.e. the cookies of the domain and its
+    /// subdomains are kept. This call updates both the SQLite store and
+    /// the internal `cookies` vector. Returns the number of removed cookies.
+    pub fn delete_except_domain(
+        &mut self,
+        domain: &str,
+    ) -> Result<usize, DbError> {
+        let domain = domain.trim_start_matches('.');
+
+        // Underscores can occur in hostnames but are wildcards for LIKE
+        let pattern = format!("%{}%", domain.replace('_', "\\_"));
+        let query = format!(
+            "DELETE FROM {} WHERE {} NOT LIKE ?1 ESCAPE '\\';",
+            self.table_name(),
+            self.column("Host"),
+        );
+        debugln!("{}", query);
+        let audit = AuditEntry {
+            action: "keep-domain",
+            host: domain,
+            name: "*",
+        };
+        self.execute(&query, [pattern], audit)?;
+
+        let count = self.cookies.len();
+        self.cookies.retain(|c| c.host.contains(domain));
+        self.cookie_count = self.cookies.len();
+        self.rebuild_index();
+
+        Ok(count - self.cookie_count)
+    }
+
+    /// Index the hosts and names of the loaded cookies
+    fn rebuild_index(&mut self) {
+        self.domain_index =
+            SearchIndex::new(self.cookies.iter().map(|c| c.host.as_str()));
+        self.name_index =
+            SearchIndex::new(self.cookies.iter().map(|c| c.name.as_str()));
+    }
+
+    /// Set the expiry of a cookie with a specific name from a domain or
+    /// of ALL cookies from a domain if no name is specified to a
+    /// timestamp in the past. Expired cookies are removed by the browser
+    /// on its next launch. The `origin_attributes` parameter is
+    /// interpreted in the same way as for `delete_from_domain()`.
+    /// This call updates both the SQLite store and the internal
+    /// `cookies` vector.
+    pub fn expire_from_domain(
+        &mut self,
+        domain: &str,
+        name: &str,
+        origin_attributes: Option<&str>,
+    ) -> Result<(), DbError> {
+        // An expiry of 0 denotes a session cookie, use the second after
+        // the UNIX epoch instead
+        let expired = 1;
+        let expiry_native = self.get_native_timestamp(expired);
+
+        let mut query = format!(
+            "UPDATE {} SET {} = ?1 WHERE {} == ?2",
+            self.table_name(),
+            self.column("Expiry"),
+            self.column("Host"),
+        );
+        let mut params: Vec<&dyn rusqlite::ToSql> =
+            vec![&expiry_native, &domain];
+        if !name.is_empty() {
+            params.push(&name);
+            query +=
+                &format!(" AND {} == ?{}", self.column("Name"), params.len());
+        }
+        let origin_param =
+            origin_attributes.filter(|_| self.typing == DbType::Firefox);
+        if let Some(attrs) = &origin_param {
+            params.push(attrs);
+            query += &format!(" AND originAttributes == ?{}", params.len());
+        }
+        query += ";";
+        let audit = AuditEntry {
+            action: "expire",
+            host: domain,
+            name: if name.is_empty() { "*" } else { name },
+        };
+        self.execute(&query, rusqlite::params_from_iter(params), audit)?;
+
+        self.cookies
+            .iter_mut()
+            .filter(|c| c.matches_scope(domain, name, origin_attributes))
+            .for_each(|c| c.expiry = expired);
+
+        Ok(())
+    }
+
+    /// Reorder the `cookies` vector according to `order`,
+    /// `SortOrder::Name` retains the order from the database.
+    pub fn sort_cookies(&mut self, order: SortOrder) {
+        if order == SortOrder::LastAccess {
+            self.cookies.sort_by_key(|c| cmp::Reverse(c.last_access));
+        }
+    }
+
+    /// All cookies for which `pred` returns true, e.g.
+    /// ```ignore
+    /// let expired = cdb.cookies_matching(|c| c.expiry != 0 && c.expiry < now);
+    /// ```
+    /// References are returned to avoid copying the loaded cookies,
+    /// this is the building block for filtering cookies by any field.
+    pub fn cookies_matching<F: Fn(&Cookie) -> bool>(
+        &self,
+        pred: F,
+    ) -> Vec<&Cookie> {
+        self.cookies.iter().filter(|c| pred(c)).collect()
+    }
+
+    /// Return the cookie with the given `rowid`
+    pub fn cookie_by_rowid(&self, rowid: i64) -> Option<&Cookie> {
+        self.cookies.iter().find(|c| c.rowid == rowid)
+    }
+
+    /// The display name of the Firefox container that a cookie
+    /// belongs to, `None` for cookies outside of a container
+    pub fn container_name(&self, cookie: &Cookie) -> Option<String> {
+        let id = cookie.container_id()?;
+        Some(
+            self.containers
+                .get(&id)
+                .cloned()
+                .unwrap_or_else(|| format!("Container {id}")),
+        )
+    }
+
+    /// Load the names of each Firefox container from the `containers.json`
+    /// file in the profile directory (if it exists)
+    fn load_containers(&mut self) {
+        let Some(filepath) =
+            self.path.parent().map(|p| p.join(FIREFOX_CONTAINERS))
+        else {
+            return;
+        };
+        let Ok(content) = std::fs::read_to_string(&filepath) else {
+            return;
+        };
+        match parse_containers(&content) {
+            Ok(containers) => self.containers = containers,
+            Err(e) => debugln!("{}: {}", filepath.display(), e),
+        }
+    }
+}
+
+/// Deduplicated list of domains ordered according to `order`, the cookies
+/// can originate from several databases.
+/// For `SortOrder::LastAccess`, a domain is ordered by the most recent
+/// access time of any of its cookies. Domains in `pinned` are placed
+/// first, in the same order. With `flatten`, the site of each host is
+/// listed instead.
+pub fn order_domains<'a, I: Iterator<Item = &'a Cookie>>(
+    cookies: I,
+    order: SortOrder,
+    pinned: &HashSet<String>,
+    flatten: bool,
+) -> Vec<String> {
+    let mut last_access: HashMap<&str, i64> = HashMap::new();
+    for c in cookies {
+        let host = if flatten { c.site() } else { &c.host };
+        let t = last_access.entry(host).or_insert(c.last_access);
+        *t = cmp::max(*t, c.last_access);
+    }
+    let mut hst_names = Vec::from_iter(last_access);
+    match order {
+        SortOrder::Name => hst_names.sort_by(|a, b| a.0.cmp(b.0)),
+        SortOrder::LastAccess => {
+            hst_names.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)))
+        }
+    }
+    // Stable sort, keeps the order within pinned and unpinned domains
+    hst_names.sort_by_key(|(h, _)| !pinned.contains(*h));
+    hst_names.iter().map(|(h, _)| h.to_string()).collect()
+}
+
+/// Lazily yield every loaded cookie across several databases together
+/// with the database it belongs to, e.g.
+/// ```ignore
+/// for (cdb, cookie) in iter_all(&cookie_dbs) {
+///     println!("{}: {}", cdb.path_short(), cookie.name);
+/// }
+/// ```
+/// Databases are visited in order and only cookies that have been loaded
+/// with `load_cookies()` are included.
+pub fn iter_all(
+    cookie_dbs: &[CookieDB],
+) -> impl Iterator<Item = (&CookieDB, &Cookie)> {
+    cookie_dbs
+        .iter()
+        .flat_map(|cdb| cdb.cookies.iter().map(move |c| (cdb, c)))
+}
+
+/// Order the `matches` from all profiles by `order` and keep the first
+/// `limit`, returns the number of matches before they were truncated.
+/// Profiles keep their order for `SortOrder::Name`.
+pub fn limit_matches<'a>(
+    matches: &mut Vec<(&'a CookieDB, &'a Cookie)>,
+    order: SortOrder,
+    limit: Option<usize>,
+) -> usize {
+    let matched = matches.len();
+    if order == SortOrder::LastAccess {
+        // A stable sort keeps the profile order for equal timestamps
+        matches.sort_by_key(|(_, c)| cmp::Reverse(c.last_access));
+    }
+    if let Some(limit) = limit {
+        matches.truncate(limit);
+    }
+    matched
+}
+
+/// Parse the `identities` from a Firefox `containers.json` file into
+/// a map from `userContextId` to the name of each container.
+/// The default containers only have a localisation ID.
+pub fn parse_containers(
+    content: &str,
+) -> Result<HashMap<u32, String>, serde_json::Error> {
+    let root: serde_json::Value = serde_json::from_str(content)?;
+    let mut containers = HashMap::new();
+    let Some(identities) = root["identities"].as_array() else {
+        return Ok(containers);
+    };
+    for identity in identities {
+        let Some(id) = identity["userContextId"].as_u64() else {
+            continue;
+        };
+        let name =
+            match (identity["name"].as_str(), identity["l10nID"].as_str()) {
+                (Some(name), _) => name.to_string(),
+                (None, Some("userContextPersonal.label")) => {
+                    "Personal".to_string()
+                }
+                (None, Some("userContextWork.label")) => "Work".to_string(),
+                (None, Some("userContextBanking.label")) => {
+                    "Banking".to_string()
+                }
+                (None, Some("userContextShopping.label")) => {
+                    "Shopping".to_string()
+                }
+                _ => continue,
+            };
+        containers.insert(id as u32, name);
+    }
+    Ok(containers)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::cookie_db::CookieDB;
-    use crate::path::PathBuf;
-    use crate::util::{get_home, DbType};
+    use std::collections::{HashMap, HashSet};
+    use std::path::PathBuf;
+
+    use crate::config::{
+        Config, SortOrder, CHROME_EPOCH_OFFSET, CONFIG, FIREFOX_CONTAINERS,
+        INVALID_TIMESTAMP,
+    };
+    use crate::cookie_db::{iter_all, limit_matches, order_domains, CookieDB};
+    use crate::error::DbError;
+    use crate::fixtures::{TempDir, CHROME_SCHEMA, FIREFOX_SCHEMA};
+    use crate::search::SearchIndex;
+    use crate::util::{get_home, AuditEntry, DbType};
 
     #[test]
     fn test_path_short() {
@@ -264,6 +918,13 @@ mod tests {
             path: PathBuf::from("./cookies.sqlite"),
             typing: DbType::Chrome,
             cookies: vec![],
+            readonly: false,
+            cookie_count: 0,
+            containers: HashMap::new(),
+            search_dir: None,
+            modified: 0,
+            domain_index: SearchIndex::default(),
+            name_index: SearchIndex::default(),
         };
         assert_eq!(cdb.path_short(), "./cookies.sqlite");
 
@@ -276,4 +937,363 @@ mod tests {
         ));
         assert_eq!(cdb.path_short(), "~/.config/chromium/Default");
     }
+
+    #[test]
+    fn test_profile_name() {
+        CONFIG.get_or_init(Config::default);
+        let mut cdb = CookieDB::new(
+            PathBuf::from("/home/user/.config/chromium/Default/Cookies"),
+            DbType::Chrome,
+        );
+        assert_eq!(cdb.profile_name().as_deref(), Some("Default"));
+
+        cdb.path = PathBuf::from(
+            "/home/user/.config/chromium/Profile 1/Network/Cookies",
+        );
+        assert_eq!(cdb.profile_name().as_deref(), Some("Profile 1"));
+        assert!(cdb.matches_profile("Profile 1", true));
+        assert!(!cdb.matches_profile("Profile", true));
+        assert!(cdb.matches_profile("Profile", false));
+    }
+
+    #[test]
+    fn test_unix_epoch() {
+        let mut cdb = CookieDB {
+            path: PathBuf::from("Cookies"),
+            typing: DbType::Chrome,
+            cookies: vec![],
+            readonly: false,
+            cookie_count: 0,
+            containers: HashMap::new(),
+            search_dir: None,
+            modified: 0,
+            domain_index: SearchIndex::default(),
+            name_index: SearchIndex::default(),
+        };
+        // Session cookies
+        assert_eq!(cdb.get_unix_epoch(0), 0);
+        // Chrome epoch boundary
+        let offset = CHROME_EPOCH_OFFSET * 1_000_000;
+        assert_eq!(cdb.get_unix_epoch(offset), 0);
+        assert_eq!(cdb.get_unix_epoch(offset + 1_000_000), 1);
+        assert_eq!(cdb.get_unix_epoch(offset - 1_000_000), INVALID_TIMESTAMP);
+        assert_eq!(cdb.get_native_timestamp(1), offset + 1_000_000);
+        // Overflow and negative values
+        assert_eq!(cdb.get_unix_epoch(i64::MAX), INVALID_TIMESTAMP);
+        assert_eq!(cdb.get_unix_epoch(i64::MIN), INVALID_TIMESTAMP);
+        assert_eq!(cdb.get_native_timestamp(i64::MAX), i64::MAX);
+
+        cdb.typing = DbType::Firefox;
+        assert_eq!(cdb.get_unix_epoch(1_700_000_000_000_000), 1_700_000_000);
+        assert_eq!(cdb.get_unix_epoch(-1_000_000), INVALID_TIMESTAMP);
+        assert_eq!(cdb.get_unix_epoch(i64::MAX), INVALID_TIMESTAMP);
+    }
+
+    #[test]
+    fn test_containers() {
+        CONFIG.get_or_init(Config::default);
+        let dir = TempDir::new("containers");
+
+        // The same cookie in the default context and two containers
+        let path = dir.sqlite(
+            "cookies.sqlite",
+            &format!(
+                "{FIREFOX_SCHEMA}
+                INSERT INTO moz_cookies VALUES
+                    (1, '', 'sid', 'a', '.example.com', '/', 0, 0, 0, 0, 0, 0),
+                    (2, '^userContextId=1', 'sid', 'b', '.example.com', '/',
+                     0, 0, 0, 0, 0, 0),
+                    (3, '^userContextId=5', 'sid', 'c', '.example.com', '/',
+                     0, 0, 0, 0, 0, 0);"
+            ),
+        );
+        std::fs::write(
+            dir.path().join(FIREFOX_CONTAINERS),
+            r#"{"identities": [
+                {"userContextId": 1, "l10nID": "userContextPersonal.label"},
+                {"userContextId": 5, "name": "Shop"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let mut cdb = CookieDB::new(path.clone(), DbType::Firefox);
+        cdb.load_cookies().unwrap();
+        assert_eq!(cdb.cookies.len(), 3);
+
+        let names: Vec<Option<String>> =
+            cdb.cookies.iter().map(|c| cdb.container_name(c)).collect();
+        assert_eq!(
+            names,
+            vec![None, Some("Personal".to_string()), Some("Shop".to_string())]
+        );
+        let cookie = cdb.cookie_by_rowid(3).unwrap();
+        assert_eq!(cookie.value, "c");
+
+        // Only the cookie from the selected container is removed
+        cdb.delete_from_domain(".example.com", "sid", Some("^userContextId=1"))
+            .unwrap();
+        assert_eq!(cdb.cookies.len(), 2);
+        assert_eq!(cdb.count_cookies().unwrap(), 2);
+        assert!(cdb.cookie_by_rowid(2).is_none());
+
+        // Expiry is scoped in the same way
+        cdb.expire_from_domain(".example.com", "sid", Some(""))
+            .unwrap();
+        cdb.load_cookies().unwrap();
+        let expiry: Vec<i64> = cdb.cookies.iter().map(|c| c.expiry).collect();
+        assert_eq!(expiry, vec![1, 0]);
+
+        // All containers are included if no origin is given
+        cdb.delete_from_domain(".example.com", "", None).unwrap();
+        assert_eq!(cdb.count_cookies().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_delete_from_domain() {
+        CONFIG.get_or_init(Config::default);
+        let dir = TempDir::new("delete");
+
+        // Names that are also column names and quotes in a name
+        let path = dir.sqlite(
+            "Cookies",
+            &format!(
+                "{CHROME_SCHEMA}
+                INSERT INTO cookies VALUES
+                    (0, '.example.com', 'name', 'a', '', '/', 0, 0, 0, 0, 0),
+                    (0, '.example.com', 'value', 'b', '', '/', 0, 0, 0, 0, 0),
+                    (0, '.example.com', 'a\"b', 'c', '', '/', 0, 0, 0, 0, 0);"
+            ),
+        );
+
+        let mut cdb = CookieDB::new(path.clone(), DbType::Chrome);
+        cdb.load_cookies().unwrap();
+        cdb.delete_from_domain(".example.com", "name", None)
+            .unwrap();
+        assert_eq!(cdb.count_cookies().unwrap(), 2);
+        cdb.delete_from_domain(".example.com", "a\"b", None)
+            .unwrap();
+        cdb.load_cookies().unwrap();
+        let names: Vec<&str> =
+            cdb.cookies.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["value"]);
+    }
+
+    #[test]
+    fn test_delete_by_rowid() {
+        CONFIG.get_or_init(Config::default);
+        let dir = TempDir::new("rowid");
+
+        // Two rows that only differ in their value
+        let path = dir.sqlite(
+            "Cookies",
+            &format!(
+                "{CHROME_SCHEMA}
+                INSERT INTO cookies VALUES
+                    (0, '.example.com', 'sid', 'a', '', '/', 0, 0, 0, 0, 0),
+                    (0, '.example.com', 'sid', 'b', '', '/', 0, 0, 0, 0, 0);"
+            ),
+        );
+
+        let mut cdb = CookieDB::new(path.clone(), DbType::Chrome);
+        cdb.load_cookies().unwrap();
+        let rowid = cdb.cookies.iter().find(|c| c.value == "a").unwrap().rowid;
+
+        cdb.delete_cookie(rowid).unwrap();
+        assert_eq!(cdb.cookies.len(), 1);
+        cdb.load_cookies().unwrap();
+        let values: Vec<&str> =
+            cdb.cookies.iter().map(|c| c.value.as_str()).collect();
+        assert_eq!(values, vec!["b"]);
+    }
+
+    #[test]
+    fn test_audit_log() {
+        CONFIG.get_or_init(Config::default);
+        let dir = TempDir::new("audit");
+        let path = dir.sqlite(
+            "Cookies",
+            &format!(
+                "{CHROME_SCHEMA}
+                INSERT INTO cookies VALUES
+                    (0, '.example.com', 'sid', 'a', '', '/', 0, 0, 0, 0, 0);"
+            ),
+        );
+        let cdb = CookieDB::new(path.clone(), DbType::Chrome);
+        let query = "DELETE FROM cookies;";
+        let audit = || AuditEntry {
+            action: "delete",
+            host: ".example.com",
+            name: "sid",
+        };
+
+        // Nothing is removed if the entry cannot be written
+        let unwritable = dir.path().join("missing/audit.log");
+        let result =
+            cdb.execute_logged(query, [], audit(), unwritable.to_str());
+        assert!(matches!(result, Err(DbError::AuditLog(..))));
+        assert_eq!(cdb.count_cookies().unwrap(), 1);
+
+        let audit_log = dir.path().join("audit.log");
+        let count = cdb
+            .execute_logged(query, [], audit(), audit_log.to_str())
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(cdb.count_cookies().unwrap(), 0);
+        let log = std::fs::read_to_string(&audit_log).unwrap();
+        assert!(log.ends_with("\tdelete\t.example.com\tsid\n"));
+    }
+
+    #[test]
+    fn test_iter_all() {
+        CONFIG.get_or_init(Config::default);
+        let dir = TempDir::new("iter");
+
+        let firefox_path = dir.sqlite(
+            "cookies.sqlite",
+            &format!(
+                "{FIREFOX_SCHEMA}
+                INSERT INTO moz_cookies VALUES
+                    (1, '', 'a', '', '.a.com', '/', 0, 0, 0, 0, 0, 0),
+                    (2, '', 'b', '', '.a.com', '/', 0, 0, 0, 0, 0, 0);"
+            ),
+        );
+        let chrome_path = dir.sqlite(
+            "Cookies",
+            &format!(
+                "{CHROME_SCHEMA}
+                INSERT INTO cookies VALUES
+                    (0, '.b.com', 'c', '', '', '/', 0, 0, 0, 0, 0);"
+            ),
+        );
+
+        let mut cdbs = vec![
+            CookieDB::new(firefox_path, DbType::Firefox),
+            CookieDB::new(chrome_path, DbType::Chrome),
+        ];
+        // Nothing is yielded before the cookies are loaded
+        assert_eq!(iter_all(&cdbs).count(), 0);
+
+        cdbs.iter_mut().for_each(|c| c.load_cookies().unwrap());
+        let names: Vec<(DbType, &str)> = iter_all(&cdbs)
+            .map(|(cdb, c)| (cdb.typing, c.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (DbType::Firefox, "a"),
+                (DbType::Firefox, "b"),
+                (DbType::Chrome, "c")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_limit_matches() {
+        CONFIG.get_or_init(Config::default);
+        let dir = TempDir::new("limit");
+
+        // The most recently accessed cookies are spread over both profiles
+        let mut cdbs = vec![];
+        for (profile, rows) in [
+            (
+                "a",
+                "(1, '', 'a1', '', '.a.com', '/', 0, 1000000000, 0, 0, 0, 0),
+                   (2, '', 'a2', '', '.a.com', '/', 0, 3000000000, 0, 0, 0, 0)",
+            ),
+            (
+                "b",
+                "(1, '', 'b1', '', '.b.com', '/', 0, 4000000000, 0, 0, 0, 0),
+                   (2, '', 'b2', '', '.b.com', '/', 0, 2000000000, 0, 0, 0, 0)",
+            ),
+        ] {
+            let path = dir.sqlite(
+                &format!("{profile}/cookies.sqlite"),
+                &format!(
+                    "{FIREFOX_SCHEMA} INSERT INTO moz_cookies VALUES {rows};"
+                ),
+            );
+            let mut cdb = CookieDB::new(path, DbType::Firefox);
+            cdb.load_cookies().unwrap();
+            cdbs.push(cdb);
+        }
+        let names = |order, limit| {
+            let mut matches: Vec<_> = iter_all(&cdbs).collect();
+            let matched = limit_matches(&mut matches, order, limit);
+            let names: Vec<String> =
+                matches.iter().map(|(_, c)| c.name.clone()).collect();
+            (names, matched)
+        };
+
+        // The limit is applied after sorting the cookies of all profiles
+        assert_eq!(
+            names(SortOrder::LastAccess, Some(2)),
+            (vec!["b1".to_string(), "a2".to_string()], 4)
+        );
+        // Profiles keep their order without a sort
+        assert_eq!(
+            names(SortOrder::Name, Some(3)),
+            (
+                vec!["a1".to_string(), "a2".to_string(), "b1".to_string()],
+                4
+            )
+        );
+        // Nothing is left out without a limit or with a larger limit
+        assert_eq!(names(SortOrder::Name, None).0.len(), 4);
+        assert_eq!(names(SortOrder::LastAccess, Some(10)).0.len(), 4);
+    }
+
+    #[test]
+    fn test_domains() {
+        CONFIG.get_or_init(Config::default);
+        let dir = TempDir::new("domains");
+        let path = dir.sqlite(
+            "Cookies",
+            "CREATE TABLE cookies (host_key TEXT, name TEXT);
+            INSERT INTO cookies VALUES
+                ('b.com', 'x'), ('.a.com', 'y'), ('b.com', 'z');",
+        );
+
+        // Only the host column is needed
+        let cdb = CookieDB::new(path, DbType::Chrome);
+        assert_eq!(cdb.domains().unwrap(), vec![".a.com", "b.com"]);
+    }
+
+    #[test]
+    fn test_base_domain() {
+        CONFIG.get_or_init(Config::default);
+        let dir = TempDir::new("base-domain");
+
+        // The public suffix list would group `app.intranet` on its own,
+        // rows without a `baseDomain` fall back to the list
+        let path = dir.sqlite(
+            "cookies.sqlite",
+            "CREATE TABLE moz_cookies (id INTEGER PRIMARY KEY,
+                originAttributes TEXT NOT NULL DEFAULT '', name TEXT,
+                value TEXT, host TEXT, path TEXT, expiry INTEGER,
+                lastAccessed INTEGER, creationTime INTEGER,
+                isSecure INTEGER, isHttpOnly INTEGER, sameSite INTEGER,
+                baseDomain TEXT);
+            INSERT INTO moz_cookies VALUES
+                (1, '', 'a', '', 'app.intranet', '/', 0, 0, 0, 0, 0, 0,
+                 'intranet'),
+                (2, '', 'b', '', '.intranet', '/', 0, 0, 0, 0, 0, 0,
+                 'intranet'),
+                (3, '', 'c', '', '.www.example.com', '/', 0, 0, 0, 0, 0, 0,
+                 '');",
+        );
+
+        let mut cdb = CookieDB::new(path, DbType::Firefox);
+        cdb.load_cookies().unwrap();
+        let sites: Vec<&str> = cdb.cookies.iter().map(|c| c.site()).collect();
+        assert_eq!(sites, vec!["intranet", "intranet", "example.com"]);
+        assert_eq!(cdb.sites().unwrap(), vec!["example.com", "intranet"]);
+
+        let domains = order_domains(
+            cdb.cookies.iter(),
+            SortOrder::Name,
+            &HashSet::new(),
+            true,
+        );
+        assert_eq!(domains, vec!["example.com", "intranet"]);
+    }
 }
@@ -0,0 +1,153 @@
+/// A cookie value in a readable form together with the encodings that
+/// were removed to produce it, outermost first
+#[derive(Debug, PartialEq, Eq)]
+pub struct Decoded {
+    pub encodings: Vec<&'static str>,
+    pub text: String,
+}
+
+impl Decoded {
+    /// Description of the decoding steps, e.g. `base64 + JSON`
+    pub fn label(&self) -> String {
+        self.encodings.join(" + ")
+    }
+}
+
+/// Decode a value that is JSON, percent-encoded or base64-encoded,
+/// returns None if no encoding was detected. The decoders are tried in
+/// that order and a percent-encoded or base64-encoded value is decoded
+/// again if the result is e.g. JSON.
+pub fn decode(value: &str) -> Option<Decoded> {
+    let mut encodings = vec![];
+    let mut text = value.to_string();
+    // Every step removes an encoding so this terminates, the limit
+    // only guards against pathological values
+    for _ in 0..4 {
+        if let Some(json) = pretty_json(&text) {
+            encodings.push("JSON");
+            text = json;
+            break;
+        } else if let Some(decoded) = percent_decode(&text) {
+            encodings.push("percent-encoding");
+            text = decoded;
+        } else if let Some(decoded) = base64_decode(&text) {
+            encodings.push("base64");
+            text = decoded;
+        } else {
+            break;
+        }
+    }
+    (!encodings.is_empty()).then_some(Decoded { encodings, text })
+}
+
+/// Pretty-print a JSON object or array, other JSON values such as
+/// numbers are left as is
+fn pretty_json(value: &str) -> Option<String> {
+    let trimmed = value.trim();
+    if !(trimmed.starts_with('{') || trimmed.starts_with('[')) {
+        return None;
+    }
+    let json: serde_json::Value = serde_json::from_str(trimmed).ok()?;
+    serde_json::to_string_pretty(&json).ok()
+}
+
+/// Decode `%XX` escapes, returns None if there are none or if the
+/// result is not printable UTF-8
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut escapes = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                escapes += 1;
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    if escapes == 0 {
+        return None;
+    }
+    printable(out)
+}
+
+/// Decode standard or URL-safe base64 with optional padding, returns
+/// None if the value is not base64 or if the result is not printable UTF-8
+fn base64_decode(value: &str) -> Option<String> {
+    let value = value.trim_end_matches('=');
+    // Short values such as `true` are valid base64 by coincidence
+    if value.len() < 8 || value.len() % 4 == 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(value.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in value.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        acc = acc << 6 | u32::from(v);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    printable(out)
+}
+
+/// The bytes as a string if they are UTF-8 without control characters
+/// other than whitespace
+fn printable(bytes: Vec<u8>) -> Option<String> {
+    let s = String::from_utf8(bytes).ok()?;
+    s.chars()
+        .all(|c| !c.is_control() || c.is_whitespace())
+        .then_some(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decode::decode;
+
+    #[test]
+    fn test_decode() {
+        let label = |v: &str| decode(v).map(|d| d.label());
+        assert_eq!(label("plain"), None);
+        assert_eq!(label("12345678"), None);
+        assert_eq!(label("100%"), None);
+
+        let d = decode("a%20b%2Fc").unwrap();
+        assert_eq!(
+            (d.label().as_str(), d.text.as_str()),
+            ("percent-encoding", "a b/c")
+        );
+
+        // `{"a":1}` in base64 without padding
+        let d = decode("eyJhIjoxfQ").unwrap();
+        assert_eq!(d.label(), "base64 + JSON");
+        assert_eq!(d.text, "{\n  \"a\": 1\n}");
+
+        assert_eq!(
+            label("%7B%22a%22%3A1%7D").unwrap(),
+            "percent-encoding + JSON"
+        );
+        assert_eq!(label("aGVsbG8gd29ybGQ=").unwrap(), "base64");
+        // Binary data is not shown as text
+        assert_eq!(label("AAECAwQFBgc="), None);
+    }
+}
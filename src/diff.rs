@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use serde_json::json;
+
+use crate::cookie::Cookie;
+
+/// Cookies from two profiles are matched on their host, name and path,
+/// Firefox cookies from different containers are never matched
+type CookieKey<'a> = (&'a str, &'a str, &'a str, &'a str);
+
+fn key(c: &Cookie) -> CookieKey<'_> {
+    (&c.host, &c.name, &c.path, &c.origin_attributes)
+}
+
+/// Differences between the cookies of two profiles, `a` and `b`
+pub struct CookieDiff<'a> {
+    pub only_a: Vec<&'a Cookie>,
+    pub only_b: Vec<&'a Cookie>,
+    /// Cookies present in both profiles with a different value or expiry
+    pub changed: Vec<(&'a Cookie, &'a Cookie)>,
+}
+
+impl<'a> CookieDiff<'a> {
+    pub fn new(a: &'a [Cookie], b: &'a [Cookie]) -> Self {
+        let a_keys: HashMap<CookieKey, &Cookie> =
+            a.iter().map(|c| (key(c), c)).collect();
+        let b_keys: HashMap<CookieKey, &Cookie> =
+            b.iter().map(|c| (key(c), c)).collect();
+
+        let mut only_a: Vec<&Cookie> = a_keys
+            .iter()
+            .filter(|(k, _)| !b_keys.contains_key(*k))
+            .map(|(_, c)| *c)
+            .collect();
+        let mut only_b: Vec<&Cookie> = b_keys
+            .iter()
+            .filter(|(k, _)| !a_keys.contains_key(*k))
+            .map(|(_, c)| *c)
+            .collect();
+        let mut changed: Vec<(&Cookie, &Cookie)> = a_keys
+            .iter()
+            .filter_map(|(k, ca)| b_keys.get(k).map(|cb| (*ca, *cb)))
+            .filter(|(ca, cb)| ca.value != cb.value || ca.expiry != cb.expiry)
+            .collect();
+
+        only_a.sort_by(|x, y| key(x).cmp(&key(y)));
+        only_b.sort_by(|x, y| key(x).cmp(&key(y)));
+        changed.sort_by(|x, y| key(x.0).cmp(&key(y.0)));
+
+        CookieDiff {
+            only_a,
+            only_b,
+            changed,
+        }
+    }
+
+    /// One section per kind of difference, empty sections are omitted
+    pub fn to_text(&self, name_a: &str, name_b: &str) -> String {
+        let mut output = String::new();
+        if !self.only_a.is_empty() {
+            output += &format!("Only in {name_a}:\n");
+            for c in self.only_a.iter() {
+                output += &format!("  {} {} {}\n", c.host, c.name, c.path);
+            }
+        }
+        if !self.only_b.is_empty() {
+            output += &format!("Only in {name_b}:\n");
+            for c in self.only_b.iter() {
+                output += &format!("  {} {} {}\n", c.host, c.name, c.path);
+            }
+        }
+        if !self.changed.is_empty() {
+            output += "Changed:\n";
+            for (ca, cb) in self.changed.iter() {
+                let mut fields = vec![];
+                if ca.value != cb.value {
+                    fields.push("value");
+                }
+                if ca.expiry != cb.expiry {
+                    fields.push("expiry");
+                }
+                output += &format!(
+                    "  {} {} {} ({})\n",
+                    ca.host,
+                    ca.name,
+                    ca.path,
+                    fields.join(", ")
+                );
+            }
+        }
+        output
+    }
+
+    pub fn to_json(&self) -> String {
+        let changed: Vec<_> = self
+            .changed
+            .iter()
+            .map(|(ca, cb)| json!({ "a": ca, "b": cb }))
+            .collect();
+        let diff = json!({
+            "only_a": self.only_a,
+            "only_b": self.only_b,
+            "changed": changed,
+        });
+        serde_json::to_string_pretty(&diff).expect("Failed to serialise diff")
+            + "\n"
+    }
+}
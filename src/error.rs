@@ -0,0 +1,64 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::config::ExitError;
+
+/// Failures when reading cookie databases, input files and when copying
+/// to the clipboard. Callers can e.g. skip files that are not cookie
+/// databases during a scan but abort if a --file cannot be read.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{}: no such file", .0.display())]
+    NotFound(PathBuf),
+    #[error("{}: permission denied", .0.display())]
+    PermissionDenied(PathBuf),
+    /// The file is not an SQLite database with a cookie table
+    #[error("{}: not a cookie database", .0.display())]
+    NotADatabase(PathBuf),
+    /// A line of a domain list that can not be a domain
+    #[error("line {line}: invalid domain '{domain}'")]
+    InvalidDomain { line: usize, domain: String },
+    /// The program used by a clipboard backend is not installed
+    #[error("clipboard program '{0}' not found")]
+    MissingProgram(&'static str),
+    /// An output file exists and overwriting was not allowed
+    #[error("{}: already exists", .0.display())]
+    AlreadyExists(PathBuf),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Failures when modifying a cookie database
+#[derive(Debug, Error)]
+pub enum DbError {
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    /// The modification was not made since it could not be recorded in
+    /// the --audit-log
+    #[error("failed to write audit log {0}: {1}")]
+    AuditLog(String, io::Error),
+}
+
+impl Error {
+    /// Attach the path of a file to an I/O error
+    pub fn with_path(path: &Path, e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => Error::NotFound(path.to_path_buf()),
+            io::ErrorKind::PermissionDenied => {
+                Error::PermissionDenied(path.to_path_buf())
+            }
+            _ => Error::Io(e),
+        }
+    }
+
+    /// The exit code to use if the error terminates the program
+    pub fn exit_error(&self) -> ExitError {
+        match self {
+            Error::AlreadyExists(_) => ExitError::Write,
+            Error::MissingProgram(_) => ExitError::General,
+            _ => ExitError::Read,
+        }
+    }
+}
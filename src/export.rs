@@ -0,0 +1,245 @@
+use std::path::Path;
+
+use chrono::{TimeZone, Utc};
+use serde_json::json;
+
+use crate::cookie::Cookie;
+
+/// Output formats for the `cookies` subcommand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Fields,
+    Netscape,
+    Json,
+    Csv,
+    Curl,
+    SetCookie,
+    Puppeteer,
+}
+
+/// Version of the JSON and CSV output, this needs to be
+/// incremented whenever the serialised shape of a `Cookie` changes
+pub const EXPORT_SCHEMA_VERSION: u32 = 5;
+
+/// Name and description of each valid `--format` value
+pub const EXPORT_FORMATS: &[(&str, &str)] = &[
+    ("fields", "The fields given by --fields (default)"),
+    ("netscape", "Netscape cookie file, as used by curl and wget"),
+    ("json", "JSON object with all fields of each cookie"),
+    (
+        "csv",
+        "Comma separated values with a version comment and a header row",
+    ),
+    ("curl", "One `curl --cookie` command per domain"),
+    ("set-cookie", "One `Set-Cookie` header per cookie"),
+    (
+        "puppeteer",
+        "JSON array in the cookie format of Puppeteer and devtools",
+    ),
+];
+
+impl ExportFormat {
+    /// Parse a format from one of the names in `EXPORT_FORMATS`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fields" => Some(ExportFormat::Fields),
+            "netscape" => Some(ExportFormat::Netscape),
+            "json" => Some(ExportFormat::Json),
+            "csv" => Some(ExportFormat::Csv),
+            "curl" => Some(ExportFormat::Curl),
+            "set-cookie" => Some(ExportFormat::SetCookie),
+            "puppeteer" => Some(ExportFormat::Puppeteer),
+            _ => None,
+        }
+    }
+
+    /// Pick a format from the extension of `path`, files with an
+    /// unknown extension are written in the Netscape format
+    pub fn from_path(path: &str) -> Self {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("json") => ExportFormat::Json,
+            Some("csv") => ExportFormat::Csv,
+            _ => ExportFormat::Netscape,
+        }
+    }
+}
+
+/// Replace the value of a cookie with a placeholder, empty values are kept.
+/// The length of an encrypted (Chrome) value is used if the
+/// plaintext value is empty.
+pub fn anonymize(cookie: &mut Cookie) {
+    let len = if cookie.value.is_empty() {
+        cookie.encrypted_value.len()
+    } else {
+        cookie.value.len()
+    };
+    if len > 0 {
+        cookie.value = format!("<REDACTED:len={len}>");
+        cookie.encrypted_value.clear();
+    }
+}
+
+/// Serialise cookies into the given format, the `Fields` format is
+/// handled by `Cookie::fields_as_str()` and yields an empty string.
+pub fn format_cookies(cookies: &[Cookie], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Fields => String::new(),
+        ExportFormat::Netscape => netscape(cookies),
+        ExportFormat::Json => {
+            let export = json!({
+                "schema_version": EXPORT_SCHEMA_VERSION,
+                "tool_version": env!("CARGO_PKG_VERSION"),
+                "cookies": cookies,
+            });
+            serde_json::to_string_pretty(&export)
+                .expect("Failed to serialise cookies")
+                + "\n"
+        }
+        ExportFormat::Csv => csv(cookies),
+        ExportFormat::Curl => curl(cookies),
+        ExportFormat::SetCookie => set_cookie(cookies),
+        ExportFormat::Puppeteer => puppeteer(cookies),
+    }
+}
+
+/// https://curl.se/docs/http-cookies.html
+fn netscape(cookies: &[Cookie]) -> String {
+    let mut output = String::from("# Netscape HTTP Cookie File\n");
+    for c in cookies {
+        let host = if c.http_only {
+            format!("#HttpOnly_{}", c.host)
+        } else {
+            c.host.to_owned()
+        };
+        output += &format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            host,
+            bool_upper(c.host.starts_with('.')),
+            c.path,
+            bool_upper(c.secure),
+            c.expiry,
+            c.name,
+            c.value
+        );
+    }
+    output
+}
+
+/// The first line is a comment with the schema and tool version
+fn csv(cookies: &[Cookie]) -> String {
+    let mut output = format!(
+        "# schema_version={},tool_version={}\n",
+        EXPORT_SCHEMA_VERSION,
+        env!("CARGO_PKG_VERSION")
+    );
+    output += "host,name,value,path,creation,expiry,last_access,\
+               http_only,secure,samesite\n";
+    for c in cookies {
+        output += &format!(
+            "{},{},{},{},{},{},{},{},{},{}\n",
+            csv_escape(&c.host),
+            csv_escape(&c.name),
+            csv_escape(&c.value),
+            csv_escape(&c.path),
+            c.creation,
+            c.expiry,
+            c.last_access,
+            c.http_only,
+            c.secure,
+            c.samesite
+        );
+    }
+    output
+}
+
+/// Quote a CSV field if it contains a separator, quote or newline
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Create one `curl` invocation per domain with all of its cookies
+fn curl(cookies: &[Cookie]) -> String {
+    let mut hosts: Vec<&str> =
+        cookies.iter().map(|c| c.host.as_str()).collect();
+    hosts.sort();
+    hosts.dedup();
+
+    let mut output = String::new();
+    for host in hosts {
+        let pairs: Vec<String> = cookies
+            .iter()
+            .filter(|c| c.host == host)
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect();
+        output += &format!(
+            "curl --cookie '{}' 'https://{}/'\n",
+            pairs.join("; ").replace('\'', "'\\''"),
+            host.trim_start_matches('.')
+        );
+    }
+    output
+}
+
+fn set_cookie(cookies: &[Cookie]) -> String {
+    let mut output = String::new();
+    for c in cookies {
+        output += &format!("Set-Cookie: {}={}", c.name, c.value);
+        // Host-only cookies are stored without a leading dot and
+        // have no `Domain` attribute
+        if let Some(domain) = c.host.strip_prefix('.') {
+            output += &format!("; Domain={domain}");
+        }
+        output += &format!("; Path={}", c.path);
+        // Session cookies have no `Expires` attribute
+        if c.expiry != 0 {
+            if let Some(expires) = Utc.timestamp_opt(c.expiry, 0).single() {
+                output += &format!(
+                    "; Expires={}",
+                    expires.format("%a, %d %b %Y %H:%M:%S GMT")
+                );
+            }
+        }
+        if c.secure {
+            output += "; Secure";
+        }
+        if c.http_only {
+            output += "; HttpOnly";
+        }
+        output += &format!("; SameSite={}\n", c.samesite_str());
+    }
+    output
+}
+
+/// https://pptr.dev/api/puppeteer.cookie, session cookies
+/// have an `expires` value of -1
+fn puppeteer(cookies: &[Cookie]) -> String {
+    let export: Vec<serde_json::Value> = cookies
+        .iter()
+        .map(|c| {
+            json!({
+                "name": c.name,
+                "value": c.value,
+                "domain": c.host,
+                "path": c.path,
+                "expires": if c.is_session() { -1 } else { c.expiry },
+                "httpOnly": c.http_only,
+                "secure": c.secure,
+                "sameSite": c.samesite_str(),
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&export).expect("Failed to serialise cookies")
+        + "\n"
+}
+
+fn bool_upper(b: bool) -> &'static str {
+    if b {
+        "TRUE"
+    } else {
+        "FALSE"
+    }
+}
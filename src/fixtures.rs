@@ -0,0 +1,59 @@
+//! Fixtures shared by the unit tests
+use std::path::{Path, PathBuf};
+
+/// Columns of a Firefox cookie table, rows are added with
+/// `INSERT INTO moz_cookies VALUES (id, originAttributes, name, value, host,
+/// path, expiry, lastAccessed, creationTime, isSecure, isHttpOnly, sameSite)`
+pub const FIREFOX_SCHEMA: &str = "CREATE TABLE moz_cookies (
+    id INTEGER PRIMARY KEY, originAttributes TEXT NOT NULL DEFAULT '',
+    name TEXT, value TEXT, host TEXT, path TEXT, expiry INTEGER,
+    lastAccessed INTEGER, creationTime INTEGER, isSecure INTEGER,
+    isHttpOnly INTEGER, sameSite INTEGER);";
+
+/// Columns of a Chrome cookie table, rows are added with
+/// `INSERT INTO cookies VALUES (creation_utc, host_key, name, value,
+/// encrypted_value, path, expires_utc, is_secure, is_httponly,
+/// last_access_utc, samesite)`
+pub const CHROME_SCHEMA: &str = "CREATE TABLE cookies (
+    creation_utc INTEGER, host_key TEXT, name TEXT, value TEXT,
+    encrypted_value BLOB DEFAULT '', path TEXT, expires_utc INTEGER,
+    is_secure INTEGER, is_httponly INTEGER, last_access_utc INTEGER,
+    samesite INTEGER);";
+
+/// A directory for the files of a test, it is removed when dropped, i.e.
+/// also when an assertion fails
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create an empty directory that is unique to `name` and the process
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "rokie-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Create an SQLite database at `name` (relative to the directory)
+    /// from the statements in `sql`
+    pub fn sqlite(&self, name: &str, sql: &str) -> PathBuf {
+        let path = self.0.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(sql).unwrap();
+        conn.close().unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::{hash_map::Entry, HashMap};
+use std::path::Path;
+
+use crossterm::event::KeyCode;
+
+use crate::config::{CONFIG_DIR, KEYMAP_FILE};
+use crate::util::app_dir;
+
+/// Actions in the normal mode of the TUI that can be bound to keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Left,
+    Right,
+    Down,
+    Up,
+    Search,
+    NextMatch,
+    PreviousMatch,
+    Delete,
+    Expire,
+    DeleteMatches,
+    KeepDomain,
+    Copy,
+    CopyDomain,
+    ToggleIdn,
+    TogglePaths,
+    ToggleSession,
+    ToggleEmpty,
+    ShowValue,
+    ClearFilter,
+    QuickJump,
+    FocusFields,
+    TogglePin,
+    ExportView,
+    CopyFields,
+    CopyJson,
+    CopyJsonAll,
+    SelectAll,
+    Reload,
+    Rescan,
+}
+
+/// Name of each action in a keymap file and its default keys
+const DEFAULT_BINDINGS: &[(&str, Action, &[&str])] = &[
+    ("quit", Action::Quit, &["q"]),
+    ("left", Action::Left, &["h", "Left"]),
+    ("right", Action::Right, &["l", "Right"]),
+    ("down", Action::Down, &["j", "Down"]),
+    ("up", Action::Up, &["k", "Up"]),
+    ("search", Action::Search, &["/"]),
+    ("next_match", Action::NextMatch, &["n"]),
+    ("previous_match", Action::PreviousMatch, &["N"]),
+    ("delete", Action::Delete, &["D"]),
+    ("expire", Action::Expire, &["E"]),
+    ("delete_matches", Action::DeleteMatches, &["X"]),
+    ("keep_domain", Action::KeepDomain, &["K"]),
+    ("copy", Action::Copy, &["C"]),
+    ("copy_domain", Action::CopyDomain, &["y"]),
+    ("toggle_idn", Action::ToggleIdn, &["u"]),
+    ("toggle_paths", Action::TogglePaths, &["P"]),
+    ("toggle_session", Action::ToggleSession, &["s"]),
+    ("toggle_empty", Action::ToggleEmpty, &["e"]),
+    ("show_value", Action::ShowValue, &["Enter"]),
+    ("clear_filter", Action::ClearFilter, &["Esc"]),
+    ("quick_jump", Action::QuickJump, &["f"]),
+    ("focus_fields", Action::FocusFields, &["Tab"]),
+    ("toggle_pin", Action::TogglePin, &["p"]),
+    ("export_view", Action::ExportView, &["w"]),
+    ("copy_fields", Action::CopyFields, &["Y"]),
+    ("copy_json", Action::CopyJson, &["J"]),
+    ("copy_json_all", Action::CopyJsonAll, &["I"]),
+    ("select_all", Action::SelectAll, &["a"]),
+    ("reload", Action::Reload, &["r"]),
+    ("rescan", Action::Rescan, &["R"]),
+];
+
+/// A single key or a chord of two keys pressed in succession, e.g. `dd`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Binding {
+    Key(KeyCode),
+    Chord(KeyCode, KeyCode),
+}
+
+impl Binding {
+    /// The first key that is pressed for the binding
+    fn first(&self) -> KeyCode {
+        match self {
+            Binding::Key(k) | Binding::Chord(k, _) => *k,
+        }
+    }
+}
+
+/// Mapping between keys and actions, loaded on startup
+pub struct Keymap {
+    actions: HashMap<Binding, Action>,
+    keys: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::parse("").expect("Invalid default keymap")
+    }
+}
+
+impl Keymap {
+    /// Load the keymap from `path` or from `KEYMAP_FILE` if no path is
+    /// given, the default keymap is used if `KEYMAP_FILE` does not exist
+    pub fn load(path: Option<&str>) -> Result<Self, String> {
+        let default_path = app_dir(CONFIG_DIR).join(KEYMAP_FILE);
+        let default_path = default_path.to_string_lossy();
+        let path = match path {
+            Some(p) => p,
+            None if Path::new(default_path.as_ref()).is_file() => &default_path,
+            None => return Ok(Keymap::default()),
+        };
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("{path}: {e}"))?;
+        Keymap::parse(&content).map_err(|e| format!("{path}: {e}"))
+    }
+
+    /// Parse a keymap file with one `action = key, ...` entry per line,
+    /// skipping blank lines and lines that start with '#'. Two characters,
+    /// e.g. `dd`, form a chord that is triggered by pressing both keys.
+    /// Actions that are not listed keep their default keys, unless a key
+    /// is used by a listed action. Binding one key to several of the
+    /// listed actions, or to both an action and the start of a chord,
+    /// is an error.
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut keys: HashMap<Action, Vec<Binding>> = HashMap::new();
+        let mut actions: HashMap<Binding, Action> = HashMap::new();
+
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((name, key_names)) = line.split_once('=') else {
+                return Err(format!("line {}: expected `action = key`", i + 1));
+            };
+            let action = action_from_name(name.trim()).ok_or_else(|| {
+                format!("line {}: unknown action '{}'", i + 1, name.trim())
+            })?;
+
+            let mut action_keys = vec![];
+            for key_name in key_names.split(',').map(|k| k.trim()) {
+                let key = parse_binding(key_name).ok_or_else(|| {
+                    format!("line {}: invalid key '{}'", i + 1, key_name)
+                })?;
+                if let Some(other) = chord_conflict(&actions, key) {
+                    return Err(format!(
+                        "line {}: '{}' conflicts with the chord of {:?}",
+                        i + 1,
+                        key_name,
+                        other
+                    ));
+                }
+                if let Some(other) = actions.insert(key, action) {
+                    if other != action {
+                        return Err(format!(
+                            "line {}: '{}' is bound to both {:?} and {:?}",
+                            i + 1,
+                            key_name,
+                            other,
+                            action
+                        ));
+                    }
+                }
+                action_keys.push(key);
+            }
+            keys.insert(action, action_keys);
+        }
+
+        // Fall back to the default keys for unbound actions
+        for (_, action, key_names) in DEFAULT_BINDINGS {
+            if keys.contains_key(action) {
+                continue;
+            }
+            let mut action_keys = vec![];
+            for key in key_names.iter().filter_map(|k| parse_binding(k)) {
+                if chord_conflict(&actions, key).is_some() {
+                    continue;
+                }
+                if let Entry::Vacant(e) = actions.entry(key) {
+                    e.insert(*action);
+                    action_keys.push(key);
+                }
+            }
+            keys.insert(*action, action_keys);
+        }
+
+        Ok(Keymap { actions, keys })
+    }
+
+    /// The action bound to a key (if any)
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        self.actions.get(&Binding::Key(key)).copied()
+    }
+
+    /// The action bound to the chord of `first` followed by `second`
+    pub fn chord_action(
+        &self,
+        first: KeyCode,
+        second: KeyCode,
+    ) -> Option<Action> {
+        self.actions.get(&Binding::Chord(first, second)).copied()
+    }
+
+    /// Whether `key` is the first key of a chord
+    pub fn starts_chord(&self, key: KeyCode) -> bool {
+        self.actions
+            .keys()
+            .any(|b| matches!(b, Binding::Chord(k, _) if *k == key))
+    }
+
+    /// Display name of the first key bound to an action, used in the footer
+    pub fn key_name(&self, action: Action) -> String {
+        self.keys
+            .get(&action)
+            .and_then(|k| k.first())
+            .map(|b| match b {
+                Binding::Key(k) => key_name(*k),
+                Binding::Chord(a, b) => key_name(*a) + &key_name(*b),
+            })
+            .unwrap_or_else(|| "-".to_string())
+    }
+}
+
+fn action_from_name(name: &str) -> Option<Action> {
+    DEFAULT_BINDINGS
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .map(|(_, action, _)| *action)
+}
+
+/// The action of an existing binding that `key` cannot be used together
+/// with, a key that starts a chord can not also be bound on its own
+fn chord_conflict(
+    actions: &HashMap<Binding, Action>,
+    key: Binding,
+) -> Option<Action> {
+    actions
+        .iter()
+        .find(|(b, _)| {
+            b.first() == key.first()
+                && matches!(
+                    (b, key),
+                    (Binding::Key(_), Binding::Chord(..))
+                        | (Binding::Chord(..), Binding::Key(_))
+                )
+        })
+        .map(|(_, action)| *action)
+}
+
+/// Parse a key or a chord of two characters
+fn parse_binding(name: &str) -> Option<Binding> {
+    if let Some(key) = parse_key(name) {
+        return Some(Binding::Key(key));
+    }
+    let mut chars = name.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(a), Some(b), None)
+            if !a.is_ascii_digit() && !b.is_ascii_digit() =>
+        {
+            Some(Binding::Chord(KeyCode::Char(a), KeyCode::Char(b)))
+        }
+        _ => None,
+    }
+}
+
+/// Parse a single character or the name of a special key.
+/// Digits are reserved for count prefixes.
+fn parse_key(name: &str) -> Option<KeyCode> {
+    match name {
+        "Left" => Some(KeyCode::Left),
+        "Right" => Some(KeyCode::Right),
+        "Up" => Some(KeyCode::Up),
+        "Down" => Some(KeyCode::Down),
+        "Enter" => Some(KeyCode::Enter),
+        "Esc" => Some(KeyCode::Esc),
+        "Tab" => Some(KeyCode::Tab),
+        "Backspace" => Some(KeyCode::Backspace),
+        "Delete" => Some(KeyCode::Delete),
+        "PageUp" => Some(KeyCode::PageUp),
+        "PageDown" => Some(KeyCode::PageDown),
+        "Space" => Some(KeyCode::Char(' ')),
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if !c.is_ascii_digit() => {
+                    Some(KeyCode::Char(c))
+                }
+                _ => None,
+            }
+        }
+    }
+}
+
+fn key_name(key: KeyCode) -> String {
+    match key {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyCode;
+
+    use crate::keymap::{Action, Keymap};
+
+    #[test]
+    fn test_keymap() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.action(KeyCode::Char('D')), Some(Action::Delete));
+        assert_eq!(keymap.action(KeyCode::Down), Some(Action::Down));
+
+        // Remapped keys replace the defaults of the action and
+        // take precedence over the defaults of other actions
+        let keymap =
+            Keymap::parse("# Comment\ndelete = d, Delete\nquit = j\n").unwrap();
+        assert_eq!(keymap.action(KeyCode::Char('d')), Some(Action::Delete));
+        assert_eq!(keymap.action(KeyCode::Delete), Some(Action::Delete));
+        assert_eq!(keymap.action(KeyCode::Char('D')), None);
+        assert_eq!(keymap.action(KeyCode::Char('j')), Some(Action::Quit));
+        assert_eq!(keymap.action(KeyCode::Down), Some(Action::Down));
+        assert_eq!(keymap.key_name(Action::Down), "Down");
+
+        assert!(Keymap::parse("delete = d\nexpire = d").is_err());
+        assert!(Keymap::parse("delete = dd\nexpire = d").is_err());
+        assert!(Keymap::parse("delete = 5").is_err());
+        assert!(Keymap::parse("unknown = x").is_err());
+    }
+
+    #[test]
+    fn test_chords() {
+        let d = KeyCode::Char('d');
+        let keymap = Keymap::parse("delete = dd\nexpire = de").unwrap();
+        assert!(keymap.starts_chord(d));
+        assert_eq!(keymap.action(d), None);
+        assert_eq!(keymap.chord_action(d, d), Some(Action::Delete));
+        assert_eq!(
+            keymap.chord_action(d, KeyCode::Char('e')),
+            Some(Action::Expire)
+        );
+        assert_eq!(keymap.key_name(Action::Delete), "dd");
+
+        // Defaults that start with the same key as a chord are dropped
+        let keymap = Keymap::parse("delete = ss").unwrap();
+        assert_eq!(keymap.action(KeyCode::Char('s')), None);
+        assert_eq!(keymap.key_name(Action::ToggleSession), "-");
+        assert!(Keymap::parse("delete = d1").is_err());
+    }
+}
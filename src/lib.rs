@@ -0,0 +1,20 @@
+//! The modules of rokie, used by the `rokie` binary and the benchmarks
+pub mod audit;
+pub mod clipboard;
+pub mod config;
+pub mod cookie;
+pub mod cookie_db;
+pub mod decode;
+pub mod diff;
+pub mod error;
+pub mod export;
+#[cfg(test)]
+mod fixtures;
+pub mod keymap;
+pub mod macros;
+pub mod search;
+pub mod state;
+pub mod stats;
+pub mod tui;
+pub mod util;
+pub mod watch;
@@ -11,40 +11,55 @@ macro_rules! msg_prefix {
 #[macro_export]
 macro_rules! errln {
     // Match a fmt literal + one or more expressions
-    ( $fmt:literal, $($x:expr),* ) => (
+    ( $fmt:literal, $($x:expr),* ) => {{
         msg_prefix!("91");
-        eprintln!($fmt, $($x)*);
-    );
+        eprintln!($fmt, $($x),*);
+        $crate::util::log_to_file("ERROR", &format!($fmt, $($x),*));
+    }};
     // Match one or more expressions
-    ( $($x:expr),* ) => (
+    ( $($x:expr),* ) => {{
         msg_prefix!("91");
-        eprintln!($($x)*);
-    )
+        eprintln!($($x),*);
+        $crate::util::log_to_file("ERROR", &format!($($x),*));
+    }}
 }
 #[macro_export]
 macro_rules! infoln {
     ( $fmt:literal, $($x:expr),* ) => (
-        msg_prefix!("94");
-        eprintln!($fmt, $($x)*);
+        if !Config::global().quiet {
+            msg_prefix!("94");
+            eprintln!($fmt, $($x),*);
+        }
     );
     // Match one or more expressions without a literal
     ( $($x:expr),* ) => (
-        msg_prefix!("94");
-        eprintln!($($x)*);
+        if !Config::global().quiet {
+            msg_prefix!("94");
+            eprintln!($($x),*);
+        }
     )
 }
 #[macro_export]
 macro_rules! debugln {
-    ( $fmt:literal, $($x:expr),* ) => (
-        if Config::global().debug {
+    ( $fmt:literal, $($x:expr),* ) => {{
+        // The TUI owns the terminal, messages only go to the debug log
+        if Config::global().debug
+            && !Config::global().quiet
+            && !Config::global().tui
+        {
             msg_prefix!("94");
-            eprintln!($fmt, $($x)*);
+            eprintln!($fmt, $($x),*);
         }
-    );
-    ( $($x:expr),* ) => (
-        if Config::global().debug {
+        $crate::util::log_to_file("DEBUG", &format!($fmt, $($x),*));
+    }};
+    ( $($x:expr),* ) => {{
+        if Config::global().debug
+            && !Config::global().quiet
+            && !Config::global().tui
+        {
             msg_prefix!("94");
-            eprintln!($($x)*);
+            eprintln!($($x),*);
         }
-    )
+        $crate::util::log_to_file("DEBUG", &format!($($x),*));
+    }}
 }
@@ -1,76 +1,365 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
+use std::io::{self, Write};
 use std::path;
+use std::time::Instant;
 
+use chrono::Utc;
 use clap::{CommandFactory, Parser};
+use serde_json::json;
 
 //=== Project imports ===//
-mod config;
-mod cookie;
-mod cookie_db;
-mod macros;
-mod state;
-mod tui;
-mod util;
-use crate::config::{
-    Args, Config, ALL_FIELDS, CONFIG, COOKIE_FIELDS, DEBUG_LOG,
+use rokie::audit::{audit, format_findings};
+use rokie::config::{
+    Args, Config, ExitError, ALL_FIELDS, CONFIG, CONFIG_DIR, COOKIE_FIELDS,
+    KEYMAP_FILE, PINNED_FILE,
 };
-use crate::cookie_db::CookieDB;
-use crate::tui::run;
-use crate::util::{
-    cookie_db_type, cookie_dbs_from_profiles, parse_whitelist,
-    process_is_running,
+use rokie::cookie_db::{iter_all, limit_matches, CookieDB};
+use rokie::diff::CookieDiff;
+use rokie::error::DbError;
+use rokie::export::{anonymize, format_cookies, ExportFormat, EXPORT_FORMATS};
+use rokie::keymap::Keymap;
+use rokie::stats::{
+    expiry_histogram, format_histogram, format_stats, ProfileStats,
 };
+use rokie::tui::run;
+use rokie::util::{
+    app_dir, cookie_db_type, cookie_dbs_from_profiles, db_error_message,
+    get_home, is_locked, load_profile, matching_profiles, modify_error_message,
+    no_profiles_message, output_writer, parse_column_map, parse_domain_list,
+    parse_whitelist, running_browser, spawn_scan, unique_search_dirs, DbType,
+};
+use rokie::{debugln, errln, infoln, msg_prefix};
 
 fn main() -> Result<(), ()> {
     // Load command line configuration arguments into a global
     let args: Args = Args::parse();
-    let cfg = Config::from_args(&args);
+    let mut cfg = Config::from_args(&args);
+    // Errors are reported once the global configuration is available
+    let column_map = args.column_map.as_ref().map(|path| {
+        std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| parse_column_map(&content))
+            .map_err(|e| format!("{path}: {e}"))
+    });
+    if let Some(Ok(column_map)) = &column_map {
+        cfg.column_map = column_map.clone();
+    }
     CONFIG.set(cfg).unwrap();
-    if Config::global().debug {
+    if let Some(Err(e)) = column_map {
+        errln!("Invalid column map: {}", e);
+        ExitError::General.exit();
+    }
+    if Config::global().debug && !Config::global().quiet {
         eprintln!("{:#?}", Config::global());
     }
 
-    // Verify that Firefox is not running since it locks the database
-    if process_is_running("firefox") {
-        errln!("Firefox needs to be closed");
-        std::process::exit(Config::global().err_exit);
+    if args.print_search_dirs {
+        let home = get_home();
+        for dir in unique_search_dirs() {
+            let path = format!("{home}/{dir}");
+            let status = if path::Path::new(&path).is_dir() {
+                "exists"
+            } else {
+                "missing"
+            };
+            println!("{path}\t{status}");
+        }
+        return Ok(());
+    }
+    if args.print_paths {
+        for (name, path) in [
+            ("keymap", app_dir(CONFIG_DIR).join(KEYMAP_FILE)),
+            ("pinned", app_dir(CONFIG_DIR).join(PINNED_FILE)),
+            ("log", Config::global().log_path()),
+        ] {
+            let status = if path.exists() { "exists" } else { "missing" };
+            println!("{name}\t{}\t{status}", path.display());
+        }
+        return Ok(());
+    }
+
+    // Refuse anything that would modify cookies in read-only mode,
+    // previews with --dry-run and --count-only are still allowed
+    let cfg = Config::global();
+    let modifies = cfg.delete_stdin
+        || cfg.delete_rowid.is_some()
+        || cfg.replace
+        || (cfg.clean && (cfg.apply || cfg.yes));
+    if cfg.readonly && modifies && !cfg.dry_run && !cfg.count_only {
+        errln!(
+            "Modifying cookies is disabled with --readonly, \
+             pass --dry-run to preview the changes"
+        );
+        ExitError::Write.exit();
+    }
+
+    // Clear the debug log before anything is written to it, all debug
+    // messages go to the log while the TUI is shown
+    if cfg.tui && cfg.debug {
+        let log_path = cfg.log_path();
+        if let Some(parent) = log_path.parent() {
+            std::fs::create_dir_all(parent)
+                .expect("Failed to create debug log directory");
+        }
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(log_path)
+            .expect("Failed to create debug log");
     }
 
     let mut cookie_dbs: HashSet<CookieDB> = HashSet::new();
 
+    // The TUI starts before the search for profiles has finished, databases
+    // are added as soon as they are found by a background scan
+    let stream_profiles = Config::global().tui
+        && args.file.is_empty()
+        && args.profile.is_empty()
+        && Config::global().profile_dir.is_none();
+
     // Parse a custom db if a --file was provided
-    if args.file != "" {
-        let custom_db_path = path::PathBuf::try_from(&args.file)
-            .expect("Could not create PathBuf from provided --file");
-        let typing = cookie_db_type(&custom_db_path.as_path())
-            .expect("Failed to determine database type of --file argument");
-        cookie_dbs.insert(CookieDB {
-            path: custom_db_path,
-            typing,
-            cookies: vec![],
-        });
-    } else {
+    if !args.file.is_empty() {
+        let custom_db_path = path::PathBuf::from(&args.file);
+        let typing =
+            cookie_db_type(custom_db_path.as_path()).unwrap_or_else(|e| {
+                errln!("Failed to read {}", e);
+                e.exit_error().exit();
+            });
+        cookie_dbs.insert(CookieDB::new(custom_db_path, typing));
+    } else if let Some(profile_dir) = &Config::global().profile_dir {
+        if !path::Path::new(profile_dir).is_dir() {
+            errln!("No such directory: {}", profile_dir);
+            ExitError::Read.exit();
+        }
+        cookie_dbs_from_profiles(&mut cookie_dbs);
+        if cookie_dbs.is_empty() {
+            errln!("No cookie database found under {}", profile_dir);
+            ExitError::NoProfiles.exit();
+        }
+    } else if !stream_profiles {
         // Fetch a set of all cookie dbs on the system
         cookie_dbs_from_profiles(&mut cookie_dbs);
     }
     let mut cookie_dbs = Vec::from_iter(cookie_dbs);
     cookie_dbs.sort();
 
-    // Explicitly note if an invalid --profile was specified
-    if args.profile != ""
-        && !cookie_dbs.iter().any(|c| {
-            c.path.to_string_lossy().to_owned().contains(&args.profile)
-        })
-    {
-        errln!("No profile matching '{}' found", args.profile);
-        std::process::exit(Config::global().err_exit);
+    // Only keep the profiles matched by --profile, an exact match on the
+    // name of a profile directory takes precedence over partial matches
+    if !args.profile.is_empty() {
+        let exact = cookie_dbs
+            .iter()
+            .any(|c| c.matches_profile(&args.profile, true));
+        cookie_dbs.retain(|c| c.matches_profile(&args.profile, exact));
+
+        // Explicitly note if an invalid --profile was specified
+        if cookie_dbs.is_empty() {
+            errln!(
+                "No profile matching '{}' found, see --list-profiles",
+                args.profile
+            );
+            ExitError::NoProfiles.exit();
+        }
+    }
+    // Commands that operate on cookies require at least one database
+    let requires_profiles = (!Config::global().fields.is_empty()
+        && !Config::global().list_fields
+        && !Config::global().list_formats)
+        || Config::global().clean
+        || (Config::global().tui && !stream_profiles)
+        || Config::global().delete_stdin
+        || Config::global().histogram
+        || Config::global().stats
+        || Config::global().grep
+        || Config::global().domains
+        || (Config::global().audit && Config::global().audit_profile.is_none())
+        || Config::global().replace;
+    if requires_profiles && cookie_dbs.is_empty() {
+        errln!(
+            "{}\nPass --file to open a cookie database directly",
+            no_profiles_message()
+        );
+        ExitError::NoProfiles.exit();
+    }
+    // The profile of --delete-rowid, which can also be a path
+    let rowid_db = Config::global()
+        .delete_rowid
+        .as_ref()
+        .map(|(profile, _)| resolve_profile(profile, &cookie_dbs));
+
+    // The browser that owns a database needs to be closed before it is
+    // modified. Firefox also locks its database against readers, which
+    // snapshots avoid, the TUI checks again before each modification.
+    if modifies && !cfg.dry_run && !cfg.count_only {
+        let types: Vec<DbType> = match &rowid_db {
+            Some(cookie_db) => vec![cookie_db.typing],
+            None => cookie_dbs.iter().map(|c| c.typing).collect(),
+        };
+        check_running_browser(&types);
+    } else if !cfg.snapshot {
+        check_running_browser(&[DbType::Firefox]);
     }
 
+    // Set if any profile had to be skipped during a write operation
+    let mut skipped_write = false;
+
     if args.list_profiles {
         infoln!("Profiles with a cookie database:");
         cookie_dbs.iter().for_each(|c| {
             println!("  {}", c.path_short());
         });
+        // Explain why each profile was found
+        for c in cookie_dbs.iter() {
+            if let Some(search_dir) = c.search_dir {
+                debugln!("{}: found under ~/{}", c.path_short(), search_dir);
+            }
+        }
+    }
+    //== Expiry histogram ==//
+    else if Config::global().histogram {
+        let now = Utc::now().timestamp();
+        for mut cookie_db in cookie_dbs {
+            cookie_db.load_cookies().unwrap_or_else(|e| {
+                exit_db_error(
+                    "load",
+                    &cookie_db.path_short(),
+                    &e,
+                    ExitError::Read,
+                );
+            });
+            infoln!("{}", cookie_db.path_short());
+            let buckets = expiry_histogram(&cookie_db.cookies, now);
+            print!("{}", format_histogram(&buckets));
+        }
+    }
+    //== Profile statistics ==//
+    else if Config::global().stats {
+        let mut stats = vec![];
+        for cookie_db in cookie_dbs {
+            stats.push(profile_stats(&cookie_db));
+        }
+        if Config::global().stats_json {
+            let output = serde_json::to_string_pretty(&stats)
+                .expect("Failed to serialise stats");
+            println!("{output}");
+        } else {
+            print!("{}", format_stats(&stats));
+        }
+    }
+    //== Delete domains read from stdin ==//
+    else if Config::global().delete_stdin {
+        let start = Instant::now();
+        let domains =
+            parse_domain_list(io::stdin().lock()).unwrap_or_else(|e| {
+                errln!("Failed to read domains from stdin: {}", e);
+                ExitError::Read.exit();
+            });
+        let mut counts = vec![];
+        let (mut profiles, mut total) = (0, 0);
+        for mut cookie_db in cookie_dbs {
+            if Config::global().count_only {
+                let count =
+                    cookie_db.count_for_domains(&domains).unwrap_or_else(|e| {
+                        exit_db_error(
+                            "count cookies in",
+                            &cookie_db.path_short(),
+                            &e,
+                            ExitError::Read,
+                        );
+                    });
+                counts.push((cookie_db.path_short(), count));
+                continue;
+            }
+            if cookie_db.readonly && !Config::global().dry_run {
+                errln!("Skipping read-only profile {}", cookie_db.path_short());
+                skipped_write = true;
+                continue;
+            }
+            cookie_db.load_cookies().unwrap_or_else(|e| {
+                exit_db_error(
+                    "load",
+                    &cookie_db.path_short(),
+                    &e,
+                    ExitError::Read,
+                );
+            });
+            let mut count = 0;
+            for domain in domains.iter() {
+                let domain = domain.trim_start_matches('.');
+                for host in [domain.to_string(), format!(".{domain}")] {
+                    let matches =
+                        cookie_db.cookies_matching(|c| c.host == host).len();
+                    if matches == 0 {
+                        continue;
+                    }
+                    cookie_db
+                        .delete_from_domain(&host, "", None)
+                        .unwrap_or_else(|e| {
+                            exit_modify_error("delete cookies from", &host, &e);
+                        });
+                    count += matches;
+                }
+            }
+            if Config::global().dry_run {
+                infoln!(
+                    "{}: {} cookie(s) would be removed",
+                    cookie_db.path_short(),
+                    count
+                );
+            } else {
+                infoln!(
+                    "{}: Removed {} cookie(s)",
+                    cookie_db.path_short(),
+                    count
+                );
+            }
+            profiles += 1;
+            total += count;
+        }
+        if Config::global().count_only {
+            print_counts(&counts);
+        } else if Config::global().dry_run {
+            print_summary(profiles, total, "would be removed", start);
+        } else {
+            print_summary(profiles, total, "removed", start);
+        }
+    }
+    //== Delete a single cookie by its rowid ==//
+    else if let (Some((_, rowid)), Some(mut cookie_db)) =
+        (&Config::global().delete_rowid, rowid_db)
+    {
+        let rowid: i64 = rowid.parse().unwrap_or_else(|_| {
+            errln!("Invalid rowid '{}'", rowid);
+            ExitError::General.exit();
+        });
+        if cookie_db.readonly && !Config::global().dry_run {
+            errln!("Profile {} is read-only", cookie_db.path_short());
+            ExitError::Write.exit();
+        }
+        cookie_db.load_cookies().unwrap_or_else(|e| {
+            exit_db_error("load", &cookie_db.path_short(), &e, ExitError::Read);
+        });
+        let Some(c) = cookie_db.cookie_by_rowid(rowid) else {
+            errln!(
+                "{}: no cookie with rowid {}",
+                cookie_db.path_short(),
+                rowid
+            );
+            ExitError::General.exit();
+        };
+        let cookie = format!("{} {} (rowid {})", c.host, c.name, rowid);
+        cookie_db.delete_cookie(rowid).unwrap_or_else(|e| {
+            exit_modify_error(
+                "delete a cookie from",
+                &cookie_db.path_short(),
+                &e,
+            );
+        });
+        if Config::global().dry_run {
+            infoln!("{}: {} would be removed", cookie_db.path_short(), cookie);
+        } else {
+            infoln!("{}: Removed {}", cookie_db.path_short(), cookie);
+        }
     }
     //== Subcmd: cookies ==//
     else if Config::global().list_fields {
@@ -78,94 +367,551 @@ fn main() -> Result<(), ()> {
         for field_name in COOKIE_FIELDS.keys() {
             println!("  {:?}", field_name);
         }
-    } else if Config::global().fields != "" && cookie_dbs.len() > 0 {
+    } else if Config::global().list_formats {
+        infoln!("Valid formats:");
+        for (name, description) in EXPORT_FORMATS {
+            println!("  {:<12}{}", name, description);
+        }
+    } else if !Config::global().fields.is_empty() && !cookie_dbs.is_empty() {
         let multiple_fields = Config::global().fields.find(",").is_some()
             || Config::global().fields == ALL_FIELDS;
+        // Never write color codes to an --output file
+        let color =
+            !Config::global().nocolor && Config::global().output.is_empty();
 
-        for mut cookie_db in cookie_dbs {
-            // Skip profiles if a specific --profile was passed
-            if args.profile != ""
-                && !cookie_db.path.to_string_lossy().contains(&args.profile)
-            {
-                continue;
+        let start = Instant::now();
+        let profiles = cookie_dbs.len();
+        let mut out =
+            output_writer(&Config::global().output, Config::global().force)
+                .unwrap_or_else(|e| {
+                    errln!("Failed to open output: {}", e);
+                    e.exit_error().exit();
+                });
+        let since = since_cutoff();
+
+        // Load all fields from each cookie database
+        for cookie_db in cookie_dbs.iter_mut() {
+            cookie_db.load_cookies().unwrap_or_else(|e| {
+                exit_db_error(
+                    "load",
+                    &cookie_db.path_short(),
+                    &e,
+                    ExitError::Read,
+                );
+            });
+            cookie_db.sort_cookies(Config::global().sort);
+        }
+        let mut matches: Vec<_> = iter_all(&cookie_dbs)
+            .filter(|(_, c)| {
+                // Skip domains if a specific --domain was passed
+                (Config::global().domain.is_empty()
+                    || c.host.contains(&Config::global().domain))
+                    // Skip cookies that were not accessed within --since
+                    && since.is_none_or(|t| c.last_access >= t)
+            })
+            .collect();
+        // The --limit applies to the cookies of all profiles together
+        let matched = limit_matches(
+            &mut matches,
+            Config::global().sort,
+            Config::global().limit,
+        );
+
+        let count = matches.len();
+        let cookies = matches.iter().map(|(cdb, c)| {
+            let mut c = (*c).to_owned();
+            if Config::global().anonymize {
+                anonymize(&mut c);
             }
-            // Skip profile headings if --no-heading
-            if !Config::global().no_heading {
-                infoln!("{}", cookie_db.path_short());
+            (*cdb, c)
+        });
+
+        if Config::global().format != ExportFormat::Fields {
+            for cookie_db in cookie_dbs.iter() {
+                // Skip profile headings if --no-heading
+                if !Config::global().no_heading {
+                    infoln!("{}", cookie_db.path_short());
+                }
             }
-            // Load all fields from each cookie database
-            cookie_db.load_cookies().expect("Failed to load cookies");
-            let mut output_str = String::new();
+            // Cookies from all profiles are written together for formats
+            // other than `Fields`
+            let exported: Vec<_> = cookies.map(|(_, c)| c).collect();
+            let output_str = format_cookies(&exported, Config::global().format);
+            write_output(&mut out, &output_str);
+        } else {
+            let cookies: Vec<_> = cookies.collect();
+            for cookie_db in cookie_dbs.iter() {
+                // Skip profile headings if --no-heading
+                if !Config::global().no_heading {
+                    infoln!("{}", cookie_db.path_short());
+                }
+                let mut output_str = String::new();
 
-            for c in cookie_db.cookies.iter() {
-                // Skip domains if a specific --domain was passed
-                if Config::global().domain == ""
-                    || c.host.contains(&Config::global().domain)
+                for (_, c) in
+                    cookies.iter().filter(|(cdb, _)| *cdb == cookie_db)
                 {
                     output_str = output_str
                         + &c.fields_as_str(
                             &Config::global().fields,
                             multiple_fields,
-                            !Config::global().nocolor,
+                            color,
                         )
                         + "\n";
 
                     if multiple_fields {
                         // Skip blankline if only one field is being printed
-                        output_str = output_str + "\n"
+                        output_str += "\n"
                     }
                 }
+                write_output(&mut out, &output_str);
             }
-            print!("{output_str}");
         }
+        out.flush().unwrap_or_else(|e| {
+            errln!("Failed to write output: {}", e);
+            ExitError::Write.exit();
+        });
+        print_summary(profiles, count, "exported", start);
+        report_limit(count, matched);
     }
     //== Subcmd: clean ==//
     else if Config::global().clean {
+        let start = Instant::now();
         let mut whitelist = vec![];
-        if Config::global().whitelist != "" {
-            let filepath = path::PathBuf::try_from(&Config::global().whitelist)
-                .expect("Failed to convert whitelist into a PathBuf");
-            whitelist = parse_whitelist(&filepath.as_path())
-                .expect("Failed to parse whitelist");
+        if !Config::global().whitelist.is_empty() {
+            let filepath = path::PathBuf::from(&Config::global().whitelist);
+            whitelist =
+                parse_whitelist(filepath.as_path()).unwrap_or_else(|e| {
+                    errln!("Failed to read whitelist: {}", e);
+                    e.exit_error().exit();
+                });
         }
 
+        // Only cookies last accessed before this timestamp are removed
+        // if --prune-older-than was passed
+        let older_than = Config::global()
+            .prune_older_than
+            .map(|days| Utc::now().timestamp() - i64::from(days) * 86400);
+        let apply = (Config::global().apply || Config::global().yes)
+            && !Config::global().dry_run;
+
+        if Config::global().count_only {
+            let mut counts = vec![];
+            for cookie_db in cookie_dbs {
+                let count = cookie_db
+                    .count_clean(&whitelist, older_than)
+                    .unwrap_or_else(|e| {
+                        exit_db_error(
+                            "count cookies in",
+                            &cookie_db.path_short(),
+                            &e,
+                            ExitError::Read,
+                        );
+                    });
+                counts.push((cookie_db.path_short(), count));
+            }
+            print_counts(&counts);
+            return Ok(());
+        }
+
+        let (mut profiles, mut total) = (0, 0);
         for cookie_db in cookie_dbs {
-            // Skip profiles if a specific --profile was passed
-            if args.profile != ""
-                && !cookie_db.path.to_string_lossy().contains(&args.profile)
-            {
+            if cookie_db.readonly && apply {
+                errln!("Skipping read-only profile {}", cookie_db.path_short());
+                skipped_write = true;
                 continue;
             }
             infoln!("Cleaning {}", cookie_db.path_short());
-            cookie_db
-                .clean(&whitelist, Config::global().apply)
-                .expect("Failed to delete cookies from database");
+            let count = cookie_db
+                .clean(&whitelist, older_than, apply)
+                .unwrap_or_else(|e| {
+                    exit_modify_error("clean", &cookie_db.path_short(), &e);
+                });
+            if apply {
+                infoln!("Removed {} cookie(s)", count);
+            } else {
+                infoln!("{} cookie(s) would be removed", count);
+            }
+            profiles += 1;
+            total += count;
         }
-        if Config::global().apply {
+        let action = if apply { "removed" } else { "would be removed" };
+        print_summary(profiles, total, action, start);
+        if apply {
             infoln!("== Deletions committed ==");
+        } else if Config::global().dry_run {
+            infoln!("No deletions performed with `--dry-run`");
         } else {
             infoln!("To perform deletions, pass `--apply`");
         }
     }
+    //== Subcmd: replace ==//
+    else if Config::global().replace {
+        let domain = Config::global().replace_domain.trim_start_matches('.');
+        let pattern = &Config::global().replace_pattern;
+        let replacement = &Config::global().replace_with;
+        if pattern.is_empty() {
+            errln!("The pattern to replace cannot be empty");
+            ExitError::General.exit();
+        }
+        let mut count = 0;
+        for mut cookie_db in cookie_dbs {
+            if cookie_db.readonly && !Config::global().dry_run {
+                errln!("Skipping read-only profile {}", cookie_db.path_short());
+                skipped_write = true;
+                continue;
+            }
+            cookie_db.load_cookies().unwrap_or_else(|e| {
+                exit_db_error(
+                    "load",
+                    &cookie_db.path_short(),
+                    &e,
+                    ExitError::Read,
+                );
+            });
+            let changes: Vec<(i64, String)> = cookie_db
+                .cookies
+                .iter()
+                .filter(|c| {
+                    c.host.trim_start_matches('.') == domain
+                        && c.value.contains(pattern.as_str())
+                })
+                .map(|c| (c.rowid, c.value.replace(pattern, replacement)))
+                .collect();
+
+            for (rowid, value) in changes {
+                if let Some(c) = cookie_db.cookie_by_rowid(rowid) {
+                    infoln!(
+                        "{}: {} {}: '{}' -> '{}'",
+                        cookie_db.path_short(),
+                        c.host,
+                        c.name,
+                        c.value,
+                        value
+                    );
+                }
+                cookie_db.set_value(rowid, &value).unwrap_or_else(|e| {
+                    exit_modify_error("update", &cookie_db.path_short(), &e);
+                });
+                count += 1;
+            }
+        }
+        if Config::global().dry_run {
+            infoln!("{} cookie(s) would be updated with `--dry-run`", count);
+        } else {
+            infoln!("Updated {} cookie(s)", count);
+        }
+    }
+    //== Subcmd: diff ==//
+    else if Config::global().diff {
+        let mut dbs: Vec<CookieDB> = Config::global()
+            .diff_profiles
+            .iter()
+            .map(|p| resolve_profile(p, &cookie_dbs))
+            .collect();
+        for c in dbs.iter_mut() {
+            c.load_cookies().unwrap_or_else(|e| {
+                exit_db_error("load", &c.path_short(), &e, ExitError::Read);
+            });
+        }
+        let diff = CookieDiff::new(&dbs[0].cookies, &dbs[1].cookies);
+        if Config::global().diff_json {
+            print!("{}", diff.to_json());
+        } else {
+            let names = &Config::global().diff_profiles;
+            print!("{}", diff.to_text(&names[0], &names[1]));
+        }
+    }
+    //== Subcmd: audit ==//
+    else if Config::global().audit {
+        let mut dbs = match &Config::global().audit_profile {
+            Some(profile) => vec![resolve_profile(profile, &cookie_dbs)],
+            None => cookie_dbs,
+        };
+        for c in dbs.iter_mut() {
+            c.load_cookies().unwrap_or_else(|e| {
+                exit_db_error("load", &c.path_short(), &e, ExitError::Read);
+            });
+        }
+        let now = Utc::now().timestamp();
+        if Config::global().audit_json {
+            let reports: Vec<_> = dbs
+                .iter()
+                .map(|c| {
+                    json!({
+                        "profile": c.path.to_string_lossy(),
+                        "findings": audit(c, now),
+                    })
+                })
+                .collect();
+            let output = serde_json::to_string_pretty(&reports)
+                .expect("Failed to serialise findings");
+            println!("{output}");
+        } else {
+            for c in dbs.iter() {
+                infoln!("{}", c.path_short());
+                print!("{}", format_findings(&audit(c, now)));
+            }
+        }
+    }
+    //== Subcmd: domains ==//
+    else if Config::global().domains {
+        let mut domains = BTreeSet::new();
+        for cookie_db in cookie_dbs.iter() {
+            let db_domains = if Config::global().flatten {
+                cookie_db.sites()
+            } else {
+                cookie_db.domains()
+            };
+            domains.extend(db_domains.unwrap_or_else(|e| {
+                exit_db_error(
+                    "read",
+                    &cookie_db.path_short(),
+                    &e,
+                    ExitError::Read,
+                );
+            }));
+        }
+        let mut out = io::stdout().lock();
+        for domain in domains {
+            write_output(&mut out, &format!("{domain}\n"));
+        }
+    }
+    //== Subcmd: grep ==//
+    else if Config::global().grep {
+        let ignore_case = Config::global().grep_ignore_case;
+        let pattern = if ignore_case {
+            Config::global().grep_pattern.to_lowercase()
+        } else {
+            Config::global().grep_pattern.clone()
+        };
+        for cookie_db in cookie_dbs.iter_mut() {
+            cookie_db.load_cookies().unwrap_or_else(|e| {
+                exit_db_error(
+                    "load",
+                    &cookie_db.path_short(),
+                    &e,
+                    ExitError::Read,
+                );
+            });
+            cookie_db.sort_cookies(Config::global().sort);
+        }
+        let since = since_cutoff();
+        let mut out = io::stdout().lock();
+        let mut matches: Vec<_> = iter_all(&cookie_dbs)
+            .filter(|(_, c)| {
+                c.contains(&pattern, ignore_case, Config::global().grep_values)
+                    && since.is_none_or(|t| c.last_access >= t)
+            })
+            .collect();
+        let matched = limit_matches(
+            &mut matches,
+            Config::global().sort,
+            Config::global().limit,
+        );
+        for (cookie_db, c) in matches.iter() {
+            write_output(
+                &mut out,
+                &format!(
+                    "{}\t{}\t{}\n",
+                    cookie_db.path_short(),
+                    c.host,
+                    c.name
+                ),
+            );
+        }
+        report_limit(matches.len(), matched);
+    }
     //== Subcmd: tui ==//
     else if Config::global().tui {
-        // Clear the debug log
-        if Config::global().debug {
-            std::fs::OpenOptions::new()
-                .write(true)
-                .create(true)
-                .open(DEBUG_LOG)
-                .expect("Failed to create debug log");
-        }
-
-        cookie_dbs
-            .iter_mut()
-            .for_each(|c| c.load_cookies().expect("Failed to load cookies"));
-        run(cookie_dbs).expect("Failed to create TUI");
+        let keymap = Keymap::load(Config::global().keymap.as_deref())
+            .unwrap_or_else(|e| {
+                errln!("Invalid keymap: {}", e);
+                ExitError::General.exit();
+            });
+
+        // Profiles that fail to load are still listed with the error
+        let profiles = cookie_dbs.into_iter().map(load_profile).collect();
+        let scan = stream_profiles.then(|| spawn_scan(HashSet::new()));
+        // Profiles picked with --file or --profile are never rescanned
+        let rescan = args.file.is_empty() && args.profile.is_empty();
+        run(profiles, keymap, scan, rescan).unwrap_or_else(|e| {
+            errln!("Failed to create TUI: {}", e);
+            ExitError::General.exit();
+        });
     } else {
         let mut args_cmd = Args::command();
         args_cmd.print_help().unwrap();
     }
 
-    return Ok(());
+    if skipped_write {
+        ExitError::Write.exit();
+    }
+    Ok(())
+}
+
+/// Resolve a profile for `diff`, `audit` and --delete-rowid, either from a
+/// path to a cookie database or the only discovered profile that matches
+fn resolve_profile(identifier: &str, cookie_dbs: &[CookieDB]) -> CookieDB {
+    let filepath = path::Path::new(identifier);
+    if filepath.is_file() {
+        let typing = cookie_db_type(filepath).unwrap_or_else(|e| {
+            errln!("Failed to read {}", e);
+            e.exit_error().exit();
+        });
+        return CookieDB::new(filepath.to_path_buf(), typing);
+    }
+    match matching_profiles(identifier, cookie_dbs).as_slice() {
+        [c] => CookieDB::new(c.path.clone(), c.typing),
+        [] => {
+            errln!("No profile matching '{}' found", identifier);
+            ExitError::NoProfiles.exit();
+        }
+        candidates => {
+            let paths: Vec<String> = candidates
+                .iter()
+                .map(|c| format!("  {}", c.path.display()))
+                .collect();
+            errln!(
+                "'{}' matches more than one profile:\n{}",
+                identifier,
+                paths.join("\n")
+            );
+            ExitError::General.exit();
+        }
+    }
+}
+
+/// Collect the stats of a profile without loading its cookies,
+/// exits on failure
+fn profile_stats(cookie_db: &CookieDB) -> ProfileStats {
+    let (cookies, domains) = cookie_db
+        .count_cookies()
+        .and_then(|cookies| Ok((cookies, cookie_db.count_domains()?)))
+        .unwrap_or_else(|e| {
+            exit_db_error("read", &cookie_db.path_short(), &e, ExitError::Read);
+        });
+    let metadata = std::fs::metadata(&cookie_db.path).unwrap_or_else(|e| {
+        errln!("Failed to read {}: {}", cookie_db.path_short(), e);
+        ExitError::Read.exit();
+    });
+
+    // The full path is used for JSON output
+    let path = if Config::global().stats_json {
+        cookie_db.path.to_string_lossy().to_string()
+    } else {
+        cookie_db.path_short()
+    };
+    ProfileStats {
+        path,
+        browser: cookie_db.typing,
+        cookies,
+        domains,
+        modified: cookie_db.modified,
+        size: metadata.len(),
+    }
+}
+
+/// Print the number of cookies that would be removed from each profile
+/// followed by the total, exits if the total is zero and --fail-if-empty
+/// was passed
+fn print_counts(counts: &[(String, usize)]) {
+    for (profile, count) in counts {
+        println!("{count} {profile}");
+    }
+    let total: usize = counts.iter().map(|(_, count)| count).sum();
+    println!("{total} total");
+
+    if total == 0 && Config::global().fail_if_empty {
+        ExitError::NothingToRemove.exit();
+    }
+}
+
+/// Report a failed database operation and exit with `code`, or with
+/// `ExitError::Locked` if a running browser holds a lock on the database
+fn exit_db_error(
+    action: &str,
+    target: &str,
+    e: &rusqlite::Error,
+    code: ExitError,
+) -> ! {
+    errln!("{}", db_error_message(action, target, e));
+    if is_locked(e) {
+        ExitError::Locked.exit();
+    }
+    code.exit();
+}
+
+/// Report a failed modification and exit with `ExitError::Write`, or
+/// with `ExitError::Locked` if a running browser holds a lock on the
+/// database
+fn exit_modify_error(action: &str, target: &str, e: &DbError) -> ! {
+    match e {
+        DbError::Sqlite(e) => {
+            exit_db_error(action, target, e, ExitError::Write)
+        }
+        DbError::AuditLog(..) => {
+            errln!("{}", modify_error_message(action, target, e));
+            ExitError::Write.exit();
+        }
+    }
+}
+
+/// The earliest last access time of cookies that are included with --since
+fn since_cutoff() -> Option<i64> {
+    Config::global()
+        .since
+        .map(|secs| Utc::now().timestamp().saturating_sub(secs))
+}
+
+/// Print the number of processed profiles and cookies after a
+/// non-interactive operation together with the elapsed time
+fn print_summary(
+    profiles: usize,
+    cookies: usize,
+    action: &str,
+    start: Instant,
+) {
+    infoln!(
+        "{} profile(s), {} cookie(s) {} in {:.2?}",
+        profiles,
+        cookies,
+        action,
+        start.elapsed()
+    );
+}
+
+/// Exit if a browser that uses one of the given database types is running,
+/// only warn with --ignore-running
+fn check_running_browser(types: &[DbType]) {
+    let Some(browser) = running_browser(types) else {
+        return;
+    };
+    if Config::global().ignore_running {
+        errln!(
+            "Warning: {browser} is running, modifications can corrupt \
+             its database"
+        );
+    } else {
+        errln!("{browser} needs to be closed");
+        ExitError::BrowserRunning.exit();
+    }
+}
+
+/// Report how many cookies were left out by --limit (if any)
+fn report_limit(count: usize, matched: usize) {
+    if matched > count {
+        infoln!(
+            "Output limited to {} of {} cookie(s) with --limit",
+            count,
+            matched
+        );
+    }
+}
+
+/// Write to the output of the `cookies` subcommand, exits on failure
+fn write_output(out: &mut dyn Write, output_str: &str) {
+    write!(out, "{output_str}").unwrap_or_else(|e| {
+        errln!("Failed to write output: {}", e);
+        ExitError::Write.exit();
+    });
 }
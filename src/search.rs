@@ -0,0 +1,125 @@
+use std::collections::{HashMap, HashSet};
+
+/// Length of the substrings that are indexed
+const NGRAM_LEN: usize = 3;
+
+/// Index of the distinct domains or cookie names of a database, used for
+/// substring searches in the TUI. Each trigram maps to the (sorted) indices
+/// of the terms that contain it, a query is only compared against the
+/// terms that contain its rarest trigram. Queries shorter than a trigram
+/// are compared against every term.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    terms: Vec<String>,
+    ngrams: HashMap<[u8; NGRAM_LEN], Vec<usize>>,
+}
+
+impl SearchIndex {
+    pub fn new<'a, I: Iterator<Item = &'a str>>(terms: I) -> Self {
+        let mut terms: Vec<String> = terms.map(String::from).collect();
+        terms.sort();
+        terms.dedup();
+
+        let mut ngrams: HashMap<[u8; NGRAM_LEN], Vec<usize>> = HashMap::new();
+        for (i, term) in terms.iter().enumerate() {
+            for ngram in term.as_bytes().windows(NGRAM_LEN) {
+                let postings =
+                    ngrams.entry(ngram.try_into().unwrap()).or_default();
+                // Terms can contain the same trigram several times
+                if postings.last() != Some(&i) {
+                    postings.push(i);
+                }
+            }
+        }
+        SearchIndex { terms, ngrams }
+    }
+
+    /// All indexed terms that contain `query`
+    pub fn matching(&self, query: &str) -> HashSet<&str> {
+        let bytes = query.as_bytes();
+        let candidates: Box<dyn Iterator<Item = &String>> =
+            if bytes.len() < NGRAM_LEN {
+                Box::new(self.terms.iter())
+            } else {
+                let mut rarest: &[usize] = &[];
+                for ngram in bytes.windows(NGRAM_LEN) {
+                    let postings: &[usize] = self
+                        .ngrams
+                        .get(ngram)
+                        .map(|p| p.as_slice())
+                        .unwrap_or_default();
+                    if rarest.is_empty() || postings.len() < rarest.len() {
+                        rarest = postings;
+                    }
+                    // No term contains every trigram of the query
+                    if postings.is_empty() {
+                        break;
+                    }
+                }
+                Box::new(rarest.iter().map(|i| &self.terms[*i]))
+            };
+        candidates
+            .filter(|t| t.contains(query))
+            .map(|t| t.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::time::Instant;
+
+    use crate::search::SearchIndex;
+
+    #[test]
+    fn test_search_index() {
+        let terms = [".github.com", "github.com", ".gitlab.com", ".a.se"];
+        let index = SearchIndex::new(terms.into_iter());
+
+        let expected: HashSet<&str> =
+            HashSet::from([".github.com", "github.com"]);
+        assert_eq!(index.matching("github"), expected);
+        assert_eq!(index.matching("hub.c"), expected);
+        assert_eq!(index.matching("gi").len(), 3);
+        assert_eq!(index.matching("").len(), 4);
+        assert!(index.matching("gitz").is_empty());
+        assert!(index.matching("github.se").is_empty());
+    }
+
+    /// Compare the index with a linear search over a large set of domains:
+    /// `cargo test --release -- --ignored bench_search_index --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_search_index() {
+        let domains: Vec<String> = (0..50_000)
+            .map(|i| format!(".site{i}.example{}.com", i % 97))
+            .collect();
+        let queries = ["site4242.", "example13.", "e1234", "nomatch"];
+
+        let start = Instant::now();
+        let index = SearchIndex::new(domains.iter().map(|d| d.as_str()));
+        println!("Index built in {:?}", start.elapsed());
+
+        for query in queries {
+            let start = Instant::now();
+            let linear: HashSet<&str> = domains
+                .iter()
+                .filter(|d| d.contains(query))
+                .map(|d| d.as_str())
+                .collect();
+            let linear_time = start.elapsed();
+
+            let start = Instant::now();
+            let indexed = index.matching(query);
+            let indexed_time = start.elapsed();
+
+            assert_eq!(linear, indexed);
+            println!(
+                "{query:>12}: {:>5} matches, linear {linear_time:?}, \
+                 indexed {indexed_time:?}",
+                indexed.len()
+            );
+        }
+    }
+}
@@ -1,22 +1,38 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crossterm::event::KeyCode;
 use tui::widgets::ListState;
 
-use crate::config::NO_SELECTION;
+use crate::clipboard::{self, Clipboard};
+use crate::config::{
+    Config, SearchScope, SortOrder, NO_SELECTION, TUI_ALL_PROFILES,
+};
+use crate::cookie::Cookie;
 use crate::cookie_db::CookieDB;
+use crate::keymap::Keymap;
 
 pub struct StatefulList<T> {
     pub status: ListState,
     pub items: Vec<T>,
 }
 
-/// https://github.com/fdehau/tui-rs/tree/master/examples/list.rs
-impl<T> StatefulList<T> {
-    pub fn default() -> Self {
+impl<T> Default for StatefulList<T> {
+    fn default() -> Self {
         StatefulList {
             status: ListState::default(),
             items: vec![],
         }
     }
+}
+
+/// https://github.com/fdehau/tui-rs/tree/master/examples/list.rs
+impl<T> StatefulList<T> {
     pub fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         let i = match self.status.selected() {
             Some(i) => {
                 if i >= self.items.len() - 1 {
@@ -30,6 +46,9 @@ impl<T> StatefulList<T> {
         self.status.select(Some(i));
     }
     pub fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         let i = match self.status.selected() {
             Some(i) => {
                 if i == 0 {
@@ -42,6 +61,16 @@ impl<T> StatefulList<T> {
         };
         self.status.select(Some(i));
     }
+    /// Move the selection inside the bounds of `items`
+    pub fn clamp_selection(&mut self) {
+        if let Some(i) = self.status.selected() {
+            if self.items.is_empty() {
+                self.status.select(None);
+            } else if i >= self.items.len() {
+                self.status.select(Some(self.items.len() - 1));
+            }
+        }
+    }
 }
 
 #[derive(PartialEq)]
@@ -51,6 +80,66 @@ pub enum Selection {
     Cookies,
 }
 
+/// Display filter for session cookies in the `Cookies` split
+#[derive(Clone, Copy, PartialEq)]
+pub enum SessionFilter {
+    All,
+    Persistent,
+    Session,
+}
+
+impl SessionFilter {
+    /// The next mode when toggling the filter
+    pub fn next(self) -> Self {
+        match self {
+            SessionFilter::All => SessionFilter::Persistent,
+            SessionFilter::Persistent => SessionFilter::Session,
+            SessionFilter::Session => SessionFilter::All,
+        }
+    }
+
+    pub fn matches(&self, cookie: &Cookie) -> bool {
+        match self {
+            SessionFilter::All => true,
+            SessionFilter::Persistent => !cookie.is_session(),
+            SessionFilter::Session => cookie.is_session(),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SessionFilter::All => "All cookies",
+            SessionFilter::Persistent => "Persistent only",
+            SessionFilter::Session => "Session only",
+        }
+    }
+}
+
+/// Destructive actions that are only performed once confirmed
+pub enum PendingAction {
+    /// Delete all cookies from each of the listed domains in the databases
+    /// that were targeted when the prompt was shown
+    DeleteDomains(Vec<String>, Vec<PathBuf>),
+    /// Delete all cookies except those from the given domain in the
+    /// databases that were targeted when the prompt was shown
+    KeepDomain(String, Vec<PathBuf>),
+    /// Delete the marked cookies, see `State::marked`
+    DeleteMarked(Vec<(PathBuf, i64)>),
+}
+
+/// A pending action together with the prompt shown to the user
+pub struct Confirmation {
+    pub prompt: String,
+    pub action: PendingAction,
+}
+
+/// Database paths of the profiles that were listed when a rescan started
+/// and of those that have been found by it so far
+pub struct Rescan {
+    pub known: HashSet<PathBuf>,
+    pub found: HashSet<PathBuf>,
+}
+
 /// The main struct which holds the global state of the TUI
 pub struct State {
     /// The currently selected element
@@ -58,6 +147,16 @@ pub struct State {
 
     pub search_open: bool,
     pub search_field: String,
+    /// Set when the search input is used to enter the path
+    /// for an export of the current view
+    pub export_prompt: bool,
+
+    /// Set when an action awaits confirmation (y/n)
+    pub confirmation: Option<Confirmation>,
+
+    /// A message shown in place of the footer until `TUI_STATUS_DURATION`
+    /// has passed since the given instant
+    pub status_message: Option<(String, Instant)>,
 
     /// Indices of all matches from a '/' search
     pub search_matches: Vec<usize>,
@@ -65,6 +164,65 @@ pub struct State {
     /// The index in the search results
     pub selected_match: usize,
 
+    /// Only domains and cookies that contain these strings are shown,
+    /// updated while typing a query in the search input
+    pub domains_filter: String,
+    pub cookies_filter: String,
+
+    /// The last selected cookie index for each visited domain, used to
+    /// restore the selection when returning to a domain's cookies
+    pub last_cookie_idx: HashMap<String, usize>,
+
+    /// Digits typed before a motion key, e.g. `10` in `10j`
+    pub count_prefix: String,
+    /// Why the cookies of a profile could not be loaded, keyed by the
+    /// path of its database
+    pub load_errors: HashMap<PathBuf, String>,
+    /// First key of a chord and when it was pressed
+    pub pending_key: Option<(KeyCode, Instant)>,
+
+    /// Display punycode domains (`xn--`) in their Unicode form
+    pub decode_idn: bool,
+    /// Display the absolute path of each profile instead of `path_short()`,
+    /// `profiles.items` always holds the short form
+    pub full_paths: bool,
+    /// List the site of each host in the Domains split, from --flatten
+    pub flatten: bool,
+
+    /// Ordering of the domains and cookies lists
+    pub sort_order: SortOrder,
+    /// Fields matched by a search in the cookies split without a prefix
+    pub search_scope: SearchScope,
+    /// Select the first profile and field without any navigation,
+    /// disabled with --no-auto-select
+    pub auto_select: bool,
+
+    /// Show all, only persistent or only session cookies, this
+    /// does not affect which cookies are removed from a domain
+    pub session_filter: SessionFilter,
+    /// Hide cookies with an empty value, e.g. encrypted Chrome cookies
+    pub hide_empty: bool,
+    /// Domains that are always shown first, saved to `PINNED_FILE`
+    pub pinned: HashSet<String>,
+
+    /// The label characters typed so far in quick-jump mode,
+    /// `None` outside of quick-jump mode
+    pub jump_input: Option<String>,
+    /// Set when j/k move between the fields of the selected cookie
+    /// instead of between cookies in the `Cookies` split
+    pub fields_focused: bool,
+
+    /// Set when the full value of the selected cookie is shown in a popup
+    pub popup_open: bool,
+    /// Vertical and horizontal scroll offset of the popup,
+    /// clamped to the size of the content on each `ui()` tick
+    pub popup_scroll: (u16, u16),
+    /// Wrap long lines in the popup, horizontal scrolling
+    /// is only possible without wrapping
+    pub popup_wrap: bool,
+    /// Show the value in the popup with common encodings decoded
+    pub popup_decode: bool,
+
     // We we only keep the domains for the currently seleceted profile
     // in a StatefulList. If a domain is removed, we will update the
     // underlying CookieDB and reload
@@ -73,26 +231,234 @@ pub struct State {
     pub current_domains: StatefulList<String>,
     pub current_cookies: StatefulList<String>,
     pub current_fields: StatefulList<String>,
+
+    /// Set if the first profile entry is the "All profiles" view,
+    /// which combines the cookies from every database
+    pub all_profiles_entry: bool,
+    /// Set while profiles are discovered in the background
+    pub scanning: bool,
+    /// Set if profiles were discovered by a scan (rather than picked with
+    /// --file or --profile) and can be rescanned
+    pub rescan_allowed: bool,
+    /// Profiles before and during an ongoing rescan
+    pub rescan: Option<Rescan>,
+    /// The active search of the profiles list, repeated whenever
+    /// new profiles are discovered
+    pub profile_query: Option<String>,
+    /// Domain and cookie (database path and rowid) to select once the
+    /// lists have been rebuilt after new profiles were discovered
+    pub restore_domain: Option<String>,
+    pub restore_cookie: Option<(std::path::PathBuf, i64)>,
+
+    /// The index in `cookie_dbs` of each entry in `current_cookies`
+    pub current_cookie_dbs: Vec<usize>,
+    /// The `rowid` of each entry in `current_cookies`, cookies with the
+    /// same name can exist in several containers or with different paths
+    pub current_cookie_rowids: Vec<i64>,
+    /// Cookies (database path and rowid) marked with `SelectAll`, a
+    /// deletion or export applies to these instead of the current view
+    /// until the listed cookies change
+    pub marked: HashSet<(PathBuf, i64)>,
+
+    /// Keys bound to each action in normal mode
+    pub keymap: Keymap,
+
+    /// Backend used for copying, selected from the environment at startup
+    pub clipboard: Box<dyn Clipboard>,
 }
 
 impl State {
     /// Create a TUI state object from a vector of cookie databases
-    pub fn new(cookie_dbs: &Vec<CookieDB>) -> State {
-        // The profiles list will never change after launch
-        let profiles = StatefulList {
-            status: ListState::default(),
-            items: cookie_dbs.iter().map(|c| c.path_short()).collect(),
-        };
-        State {
+    pub fn new(cookie_dbs: &[CookieDB], keymap: Keymap) -> State {
+        let mut state = State {
             selection: Selection::Profiles,
             search_open: false,
             search_field: "".to_string(),
+            export_prompt: false,
+            confirmation: None,
+            status_message: None,
             search_matches: vec![],
             selected_match: NO_SELECTION,
-            profiles,
+            domains_filter: "".to_string(),
+            cookies_filter: "".to_string(),
+            last_cookie_idx: HashMap::new(),
+            count_prefix: "".to_string(),
+            pending_key: None,
+            load_errors: HashMap::new(),
+            decode_idn: false,
+            full_paths: false,
+            flatten: Config::global().flatten,
+            sort_order: Config::global().sort,
+            search_scope: Config::global().search_scope,
+            auto_select: Config::global().auto_select,
+            session_filter: SessionFilter::All,
+            hide_empty: false,
+            pinned: HashSet::new(),
+            jump_input: None,
+            fields_focused: false,
+            popup_open: false,
+            popup_scroll: (0, 0),
+            popup_wrap: true,
+            popup_decode: false,
+            profiles: StatefulList::default(),
             current_domains: StatefulList::default(),
             current_cookies: StatefulList::default(),
             current_fields: StatefulList::default(),
+            all_profiles_entry: false,
+            scanning: false,
+            rescan_allowed: false,
+            rescan: None,
+            profile_query: None,
+            restore_domain: None,
+            restore_cookie: None,
+            current_cookie_dbs: vec![],
+            current_cookie_rowids: vec![],
+            marked: HashSet::new(),
+            keymap,
+            clipboard: clipboard::detect(),
+        };
+        state.set_profiles(cookie_dbs);
+        state
+    }
+
+    /// Fill the profiles list, the list only changes while profiles are
+    /// discovered in the background after launch. An "All profiles" entry
+    /// is added if there are several profiles.
+    pub fn set_profiles(&mut self, cookie_dbs: &[CookieDB]) {
+        self.all_profiles_entry = cookie_dbs.len() > 1;
+        self.profiles.items.clear();
+        if self.all_profiles_entry {
+            self.profiles.items.push(TUI_ALL_PROFILES.to_string());
+        }
+        self.profiles
+            .items
+            .extend(cookie_dbs.iter().map(|c| c.path_short()));
+    }
+
+    /// Offset between an index in `profiles` and
+    /// the corresponding index in `cookie_dbs`
+    pub fn profile_offset(&self) -> usize {
+        usize::from(self.all_profiles_entry)
+    }
+
+    /// Indices in `cookie_dbs` for the currently selected profile,
+    /// every index is included for the "All profiles" entry
+    pub fn selected_db_indices(&self) -> Vec<usize> {
+        match self.profiles.status.selected() {
+            Some(0) if self.all_profiles_entry => {
+                (0..self.profiles.items.len() - 1).collect()
+            }
+            Some(i) => vec![i - self.profile_offset()],
+            None => vec![],
+        }
+    }
+
+    /// Index in `cookie_dbs` of the database that holds
+    /// the currently selected cookie (if any)
+    pub fn selected_cookie_db(&self) -> Option<usize> {
+        let selected_idx = self.current_cookies.status.selected()?;
+        self.current_cookie_dbs.get(selected_idx).copied()
+    }
+
+    /// The `rowid` of the currently selected cookie (if any)
+    pub fn selected_cookie_rowid(&self) -> Option<i64> {
+        let selected_idx = self.current_cookies.status.selected()?;
+        self.current_cookie_rowids.get(selected_idx).copied()
+    }
+
+    /// The list of the selected split
+    pub fn current_list(&self) -> &StatefulList<String> {
+        match self.selection {
+            Selection::Profiles => &self.profiles,
+            Selection::Domains => &self.current_domains,
+            Selection::Cookies => &self.current_cookies,
+        }
+    }
+
+    pub fn current_list_mut(&mut self) -> &mut StatefulList<String> {
+        match self.selection {
+            Selection::Profiles => &mut self.profiles,
+            Selection::Domains => &mut self.current_domains,
+            Selection::Cookies => &mut self.current_cookies,
+        }
+    }
+
+    /// The entry in the Domains split that a cookie is listed under,
+    /// several hosts share the entry of their site with --flatten
+    pub fn domain_entry<'a>(&self, cookie: &'a Cookie) -> &'a str {
+        if self.flatten {
+            cookie.site()
+        } else {
+            &cookie.host
+        }
+    }
+
+    /// Move from the `Domains` to the `Cookies` split and restore the
+    /// last selected cookie of the domain, returns false if the domain
+    /// has no cookies
+    pub fn enter_cookies(&mut self) -> bool {
+        if self.current_cookies.items.is_empty() {
+            return false;
+        }
+        let idx = self
+            .selected_domain()
+            .and_then(|d| self.last_cookie_idx.get(&d))
+            .copied()
+            .unwrap_or(0)
+            .min(self.current_cookies.items.len() - 1);
+        self.current_cookies.status.select(Some(idx));
+        self.invalidate_search();
+        self.fields_focused = false;
+        self.selection = Selection::Cookies;
+        true
+    }
+
+    /// Forget the matches of the last search, `search_matches` holds
+    /// indices into the current lists and must be invalidated whenever
+    /// items are added, removed or reordered. The marked cookies are
+    /// cleared as well since they were picked from the old view.
+    pub fn invalidate_search(&mut self) {
+        self.search_matches.clear();
+        self.selected_match = NO_SELECTION;
+        self.marked.clear();
+    }
+
+    /// Show a transient message in the footer
+    pub fn set_status(&mut self, msg: String) {
+        self.status_message = Some((msg, Instant::now()));
+    }
+
+    /// Consume the buffered count prefix, defaults to 1
+    pub fn take_count(&mut self) -> usize {
+        let count = self.count_prefix.parse().unwrap_or(1);
+        self.count_prefix.clear();
+        count
+    }
+
+    /// Set the filter of the current split to the content of the
+    /// search input. Profiles are never filtered.
+    pub fn update_filter(&mut self) {
+        self.marked.clear();
+        match self.selection {
+            Selection::Profiles => {}
+            Selection::Domains => {
+                self.domains_filter = self.search_field.clone();
+                self.current_domains.status.select(Some(0));
+            }
+            Selection::Cookies => {
+                self.cookies_filter = self.search_field.clone();
+                self.current_cookies.status.select(Some(0));
+            }
+        }
+    }
+
+    /// Clear the filter of the current split
+    pub fn clear_filter(&mut self) {
+        self.marked.clear();
+        match self.selection {
+            Selection::Profiles => self.profile_query = None,
+            Selection::Domains => self.domains_filter.clear(),
+            Selection::Cookies => self.cookies_filter.clear(),
         }
     }
 
@@ -140,4 +506,16 @@ impl State {
             None
         }
     }
+
+    /// The value of the currently selected field (if any).
+    /// Entries in the fields list are formatted as "Label: value",
+    /// the label is stripped from the returned string.
+    pub fn selected_field_value(&self) -> Option<String> {
+        let selected_idx = self.current_fields.status.selected()?;
+        let field = self.current_fields.items.get(selected_idx)?;
+        match field.split_once(": ") {
+            Some((_, value)) => Some(value.to_string()),
+            None => Some(field.to_owned()),
+        }
+    }
 }
@@ -0,0 +1,103 @@
+use chrono::{TimeZone, Utc};
+use serde::Serialize;
+
+use crate::cookie::Cookie;
+use crate::util::DbType;
+
+/// Label and upper bound (in seconds from now) of each expiry range,
+/// cookies beyond the last bound are placed in `EXPIRY_OVERFLOW`
+const EXPIRY_RANGES: &[(&str, i64)] = &[
+    ("< 1 day", 86400),
+    ("< 1 week", 7 * 86400),
+    ("< 1 month", 30 * 86400),
+    ("< 1 year", 365 * 86400),
+];
+const EXPIRY_OVERFLOW: &str = "> 1 year";
+
+/// Width of the longest bar in a histogram
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Count the cookies that expire within each range relative to `now`
+/// (a UNIX epoch timestamp). Session and already expired cookies
+/// are counted separately.
+pub fn expiry_histogram(
+    cookies: &[Cookie],
+    now: i64,
+) -> Vec<(&'static str, usize)> {
+    let mut buckets: Vec<(&str, usize)> = vec![("Session", 0), ("Expired", 0)];
+    buckets.extend(EXPIRY_RANGES.iter().map(|(label, _)| (*label, 0)));
+    buckets.push((EXPIRY_OVERFLOW, 0));
+
+    for c in cookies {
+        let idx = if c.is_session() {
+            0
+        } else if c.expiry <= now {
+            1
+        } else {
+            let remaining = c.expiry - now;
+            2 + EXPIRY_RANGES
+                .iter()
+                .position(|(_, bound)| remaining < *bound)
+                .unwrap_or(EXPIRY_RANGES.len())
+        };
+        buckets[idx].1 += 1;
+    }
+    buckets
+}
+
+/// Render a histogram as text with one bar per bucket,
+/// bars are scaled relative to the largest bucket
+pub fn format_histogram(buckets: &[(&str, usize)]) -> String {
+    let max = buckets.iter().map(|(_, n)| *n).max().unwrap_or(0).max(1);
+    let label_width = buckets
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or(0);
+
+    buckets
+        .iter()
+        .map(|(label, n)| {
+            // Non-empty buckets always get at least one block
+            let width = (n * HISTOGRAM_WIDTH).div_ceil(max);
+            let bar = "█".repeat(width);
+            format!("  {label:<label_width$} {n:>6} {bar}")
+                .trim_end()
+                .to_owned()
+                + "\n"
+        })
+        .collect()
+}
+
+/// Summary of a cookie database, based on COUNT queries
+/// and the metadata of the database file
+#[derive(Serialize)]
+pub struct ProfileStats {
+    pub path: String,
+    pub browser: DbType,
+    pub cookies: usize,
+    pub domains: usize,
+    /// Last modification of the database file in UNIX epoch time
+    pub modified: i64,
+    /// Size of the database file in bytes
+    pub size: u64,
+}
+
+/// Render the stats of each profile as text, `path` is shown as is
+pub fn format_stats(stats: &[ProfileStats]) -> String {
+    stats
+        .iter()
+        .map(|s| {
+            let modified = Utc
+                .timestamp_opt(s.modified, 0)
+                .single()
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default();
+            format!(
+                "{} ({:?})\n  Cookies:  {}\n  Domains:  {}\n  \
+                 Size:     {} bytes\n  Modified: {}\n",
+                s.path, s.browser, s.cookies, s.domains, s.size, modified
+            )
+        })
+        .collect()
+}
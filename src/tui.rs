@@ -1,3 +1,4 @@
+use chrono::Utc;
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
     execute,
@@ -6,32 +7,59 @@ use crossterm::{
         LeaveAlternateScreen,
     },
 };
-use std::{fs::OpenOptions, io, io::Write, time::Duration, time::Instant};
+use std::{
+    cmp,
+    collections::{BTreeSet, HashMap, HashSet},
+    io,
+    path::PathBuf,
+    sync::mpsc::{Receiver, TryRecvError},
+    time::Duration,
+    time::Instant,
+};
 use tui::{
     backend::{Backend, CrosstermBackend},
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::Span,
+    text::{Span, Spans},
     widgets::{
-        Block, BorderType, Borders, Cell, List, ListItem, Paragraph, Row, Table,
+        Block, BorderType, Borders, Clear, List, ListItem, Paragraph, Wrap,
     },
     Frame, Terminal,
 };
 
 use crate::{
     config::{
-        Config, DEBUG_LOG, NO_SELECTION, TUI_PRIMARY_COLOR, TUI_SEARCH,
-        TUI_TEXT_TRUNCATE_LIM,
+        Config, ProfileOrder, SearchScope, SortOrder, NO_SELECTION,
+        TUI_ALL_PROFILES, TUI_CHORD_TIMEOUT, TUI_COMPACT_WIDTH,
+        TUI_COUNT_PREFIX_LIM, TUI_EXPORT, TUI_JUMP_CHARS, TUI_POPUP_PAGE,
+        TUI_PRIMARY_COLOR, TUI_SEARCH, TUI_STATUS_DURATION,
+        TUI_TEXT_TRUNCATE_LIM, TUI_WATCH_DEBOUNCE,
+    },
+    cookie::Cookie,
+    cookie_db::{order_domains, CookieDB},
+    decode::decode,
+    error::DbError,
+    export::{format_cookies, ExportFormat},
+    keymap::{Action, Keymap},
+    search::SearchIndex,
+    state::{Confirmation, PendingAction, Rescan, Selection, State},
+    util::{
+        db_error_reason, load_pinned, modify_error_message,
+        no_profiles_message, output_writer, running_browser, save_pinned,
+        spawn_scan, write_debug_log, DbType, ScanMessage, ScanResult,
     },
-    cookie_db::CookieDB,
-    state::{Selection, State},
-    util::copy_to_clipboard,
+    watch::DbWatcher,
 };
 
 //============================================================================//
 
 /// Entrypoint for the TUI
-pub fn run(cookie_dbs: Vec<CookieDB>) -> Result<(), io::Error> {
+pub fn run(
+    profiles: Vec<ScanResult>,
+    keymap: Keymap,
+    scan: Option<Receiver<ScanMessage>>,
+    rescan: bool,
+) -> Result<(), io::Error> {
     // Disable certain parts of the terminal's default behaviour
     //  https://docs.rs/crossterm/0.23.2/crossterm/terminal/index.html#raw-mode
     enable_raw_mode()?;
@@ -43,9 +71,33 @@ pub fn run(cookie_dbs: Vec<CookieDB>) -> Result<(), io::Error> {
 
     let mut terminal = Terminal::new(backend)?;
     let tick_rate = Duration::from_millis(250);
-    let mut state = State::new(&cookie_dbs);
+    let mut load_errors = HashMap::new();
+    let mut cookie_dbs: Vec<CookieDB> = profiles
+        .into_iter()
+        .map(|(cookie_db, error)| {
+            if let Some(e) = error {
+                load_errors.insert(cookie_db.path.clone(), e);
+            }
+            cookie_db
+        })
+        .collect();
+    sort_profiles(&mut cookie_dbs);
+    let mut state = State::new(&cookie_dbs, keymap);
+    state.load_errors = load_errors;
+    state.scanning = scan.is_some();
+    state.rescan_allowed = rescan;
+    debug_log(format!("Clipboard: {}", state.clipboard.name()));
+    match load_pinned() {
+        Ok(pinned) => state.pinned = pinned,
+        Err(e) => {
+            state.set_status(format!("Failed to load pinned domains: {e}"))
+        }
+    }
+    cookie_dbs
+        .iter_mut()
+        .for_each(|c| c.sort_cookies(state.sort_order));
 
-    run_ui(&mut terminal, &mut state, cookie_dbs, tick_rate).unwrap();
+    run_ui(&mut terminal, &mut state, cookie_dbs, scan, tick_rate).unwrap();
 
     // Restore default terminal behaviour
     disable_raw_mode()?;
@@ -63,16 +115,71 @@ fn run_ui<B: Backend>(
     term: &mut Terminal<B>,
     state: &mut State,
     mut cookie_dbs: Vec<CookieDB>,
+    mut scan: Option<Receiver<ScanMessage>>,
     tick_rate: Duration,
 ) -> io::Result<()> {
     let mut last_tick = Instant::now();
 
     // Auto-select the first profile
-    if state.profiles.items.len() > 0 {
+    if state.auto_select && !state.profiles.items.is_empty() {
         state.profiles.status.select(Some(0));
     }
+    // Start with a search for the --filter of the profiles (if any)
+    if let Some(filter) = &Config::global().profile_filter {
+        search_profiles(state, &cookie_dbs, filter);
+    }
+    let mut watcher = None;
+    if Config::global().watch {
+        match DbWatcher::new(Duration::from_millis(TUI_WATCH_DEBOUNCE)) {
+            Ok(w) => watcher = Some(w),
+            Err(e) => state.set_status(format!("Failed to start --watch: {e}")),
+        }
+    }
 
     loop {
+        //== Add profiles discovered since the last tick ==//
+        if let Some(rx) = &scan {
+            let mut found = vec![];
+            loop {
+                match rx.try_recv() {
+                    Ok(ScanMessage::Profile(result)) => {
+                        if let Some(rescan) = &mut state.rescan {
+                            rescan.found.insert(result.0.path.clone());
+                        }
+                        found.push(*result)
+                    }
+                    Ok(ScanMessage::Known(path)) => {
+                        if let Some(rescan) = &mut state.rescan {
+                            rescan.found.insert(path);
+                        }
+                    }
+                    Ok(ScanMessage::Warning(msg)) => {
+                        debug_log(&msg);
+                        state.set_status(msg);
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        state.scanning = false;
+                        break;
+                    }
+                }
+            }
+            if !found.is_empty() {
+                add_profiles(state, &mut cookie_dbs, found);
+            }
+            if !state.scanning {
+                scan = None;
+                if let Some(rescan) = state.rescan.take() {
+                    finish_rescan(state, &mut cookie_dbs, rescan);
+                }
+            }
+        }
+
+        //== Reload profiles that the browser wrote to ==//
+        if let Some(watcher) = &mut watcher {
+            watch_profiles(state, &mut cookie_dbs, watcher);
+        }
+
         term.draw(|f| ui(f, state, &cookie_dbs))?;
 
         let timeout = tick_rate
@@ -80,15 +187,40 @@ fn run_ui<B: Backend>(
             .unwrap_or_else(|| Duration::from_secs(0));
 
         if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                if state.search_open {
+            let event = event::read()?;
+            // Repaint the entire screen after a resize, the layout is
+            // recomputed for the new size when the next frame is drawn
+            if let Event::Resize(_, _) = event {
+                term.autoresize()?;
+                term.clear()?;
+            }
+            if let Event::Key(key) = event {
+                if state.confirmation.is_some() {
+                    //== Confirmation prompt ==//
+                    handle_confirm_key(key.code, state, &mut cookie_dbs)
+                } else if state.jump_input.is_some() {
+                    //== Quick-jump labels ==//
+                    handle_jump_key(key.code, state)
+                } else if state.popup_open {
+                    //== Value popup ==//
+                    handle_popup_key(key.code, state, &cookie_dbs)
+                } else if state.search_open && state.export_prompt {
+                    //== Export path input ==//
+                    handle_export_key(key.code, state, &cookie_dbs)
+                } else if state.search_open {
                     //== Input mode ==//
                     handle_search_key(key.code, state, &cookie_dbs)
                 } else {
                     //== Normal mode ==//
-                    match key.code {
-                        KeyCode::Char('q') => return Ok(()),
-                        _ => handle_key(key.code, state, &mut cookie_dbs),
+                    match resolve_key(key.code, state) {
+                        Some(Action::Quit) => return Ok(()),
+                        Some(Action::Rescan) => {
+                            scan = start_rescan(state, &cookie_dbs).or(scan)
+                        }
+                        Some(action) => {
+                            handle_action(action, state, &mut cookie_dbs)
+                        }
+                        None => {}
                     }
                 }
             }
@@ -108,10 +240,13 @@ fn run_ui<B: Backend>(
 ///  |0       |1      |2           |3         |
 ///  |profiles|domains|cookie names|field_list|
 ///
+/// Terminals narrower than `TUI_COMPACT_WIDTH` only show the active
+/// list at index 0.
+///
 fn ui<B: Backend>(
     frame: &mut Frame<B>,
     state: &mut State,
-    cookie_dbs: &Vec<CookieDB>,
+    cookie_dbs: &[CookieDB],
 ) {
     // == Layout ==//
     // Split the frame vertically into a body and footer
@@ -122,202 +257,540 @@ fn ui<B: Backend>(
         )
         .split(frame.size());
 
-    // Create three chunks for the body
+    // Create three chunks for the body, or a single chunk
+    // in narrow terminals
+    let compact = frame.size().width < TUI_COMPACT_WIDTH;
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .margin(1)
-        .constraints(
-            [
+        .constraints(if compact {
+            vec![Constraint::Percentage(100)]
+        } else {
+            vec![
                 Constraint::Percentage(33),
                 Constraint::Percentage(33),
                 Constraint::Percentage(33),
             ]
-            .as_ref(),
-        )
+        })
         .split(vert_chunks[0]);
 
-    if state.search_open {
+    // Clear the status message once it has been shown long enough
+    if state.status_message.as_ref().is_some_and(|(_, t)| {
+        t.elapsed() >= Duration::from_secs(TUI_STATUS_DURATION)
+    }) {
+        state.status_message = None;
+    }
+
+    if let Some(confirmation) = &state.confirmation {
+        //== Render the confirmation prompt ==//
+        let prompt = Paragraph::new(sanitize(&confirmation.prompt))
+            .style(Style::default().fg(Color::LightRed));
+        frame.render_widget(prompt, vert_chunks[1])
+    } else if state.search_open {
         //== Render the search input ==//
         render_search(frame, state, vert_chunks[1])
+    } else if let Some((msg, _)) = &state.status_message {
+        //== Render the status message ==//
+        let status = Paragraph::new(sanitize(msg))
+            .style(Style::default().fg(Color::LightGreen));
+        frame.render_widget(status, vert_chunks[1])
     } else {
         //== Render the footer ==//
-        frame.render_widget(create_footer(), vert_chunks[1])
+        let db_indices = state.selected_db_indices();
+        let readonly = !db_indices.is_empty()
+            && db_indices.iter().all(|i| cookie_dbs[*i].readonly);
+        frame.render_widget(create_footer(state, readonly), vert_chunks[1])
     }
 
     // Determine which splits should be rendered
-    let (profiles_idx, domains_idx, cookies_idx, fields_idx) =
-        if matches!(state.selection, Selection::Cookies) {
-            (NO_SELECTION, 0, 1, 2)
-        } else {
-            (0, 1, 2, NO_SELECTION)
-        };
+    let (profiles_idx, domains_idx, cookies_idx, fields_idx) = if compact {
+        match state.selection {
+            Selection::Profiles => {
+                (0, NO_SELECTION, NO_SELECTION, NO_SELECTION)
+            }
+            Selection::Domains => (NO_SELECTION, 0, NO_SELECTION, NO_SELECTION),
+            Selection::Cookies if state.fields_focused => {
+                (NO_SELECTION, NO_SELECTION, NO_SELECTION, 0)
+            }
+            Selection::Cookies => (NO_SELECTION, NO_SELECTION, 0, NO_SELECTION),
+        }
+    } else if matches!(state.selection, Selection::Cookies) {
+        (NO_SELECTION, 0, 1, 2)
+    } else {
+        (0, 1, 2, NO_SELECTION)
+    };
 
     if profiles_idx != NO_SELECTION {
         //== Profiles ==//
-        let profile_items: Vec<ListItem> =
-            create_list_items(&state.profiles.items);
+        // The cookie count and read-only status of each profile is
+        // shown as a suffix, the suffix is not part of `state.profiles.items`
+        let mut profile_names: Vec<String> = vec![];
+        if state.all_profiles_entry {
+            let total: usize = cookie_dbs.iter().map(|c| c.cookie_count).sum();
+            profile_names.push(format!("{TUI_ALL_PROFILES} ({total})"));
+        }
+        profile_names.extend(
+            state
+                .profiles
+                .items
+                .iter()
+                .skip(state.profile_offset())
+                .zip(cookie_dbs.iter())
+                .map(|(p, c)| {
+                    let suffix = if c.readonly {
+                        format!(" ({}, read-only)", c.cookie_count)
+                    } else {
+                        format!(" ({})", c.cookie_count)
+                    };
+                    // The end of an absolute path is what tells
+                    // profiles apart, so it is kept over the start
+                    if state.full_paths {
+                        let lim = TUI_TEXT_TRUNCATE_LIM
+                            .saturating_sub(suffix.chars().count());
+                        let path = c.path.to_string_lossy();
+                        format!("{}{suffix}", truncate_start(&path, lim))
+                    } else {
+                        format!("{p}{suffix}")
+                    }
+                }),
+        );
+        // Profiles that do not match the current search are dimmed
+        let searching = state.selection == Selection::Profiles
+            && !state.search_matches.is_empty();
+        let profile_items: Vec<ListItem> = create_list_items(
+            &profile_names,
+            state.jump_input.is_some()
+                && state.selection == Selection::Profiles,
+        )
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            if searching && !state.search_matches.contains(&i) {
+                item.style(Style::default().fg(Color::DarkGray))
+            } else {
+                item
+            }
+        })
+        .collect();
 
         let profile_list = add_highlight(create_list(
             profile_items,
-            "Profiles".to_string(),
+            if state.scanning {
+                "Profiles (scanning...)".to_string()
+            } else {
+                "Profiles".to_string()
+            },
             Borders::NONE,
         ));
 
         //== Render profiles ==//
-        frame.render_stateful_widget(
-            profile_list,
-            chunks[profiles_idx],
-            &mut state.profiles.status,
-        );
+        // A completed scan without results lists the searched directories
+        // instead of an empty list
+        if cookie_dbs.is_empty() && !state.scanning {
+            let notice = Paragraph::new(no_profiles_message())
+                .block(Block::default().title("Profiles"))
+                .style(Style::default().fg(Color::LightRed));
+            frame.render_widget(notice, chunks[profiles_idx]);
+        } else {
+            frame.render_stateful_widget(
+                profile_list,
+                chunks[profiles_idx],
+                &mut state.profiles.status,
+            );
+        }
     }
 
     //== Domains ==//
-    if let Some(profile_idx) = state.profiles.status.selected() {
-        if let Some(cdb) = cookie_dbs.get(profile_idx) {
-            // Fill the current_domains state list
-            state.current_domains.items = cdb.domains();
+    // The "All profiles" entry combines the cookies of every database
+    let db_indices = state.selected_db_indices();
+    if !db_indices.is_empty() {
+        let domain_matches = domain_entry_matches(
+            state,
+            cookie_dbs,
+            &db_indices,
+            &state.domains_filter,
+        );
+        // Fill the current_domains state list
+        state.current_domains.items = order_domains(
+            db_indices
+                .iter()
+                .flat_map(|i| cookie_dbs[*i].cookies.iter()),
+            state.sort_order,
+            &state.pinned,
+            state.flatten,
+        )
+        .into_iter()
+        .filter(|d| {
+            domain_matches
+                .as_ref()
+                .is_none_or(|m| m.contains(d.as_str()))
+        })
+        .collect();
+        state.current_domains.clamp_selection();
 
-            let domain_items = create_list_items(&state.current_domains.items);
+        // Keep the same domain selected after new profiles were added
+        // or after it was pinned
+        if let Some(domain) = state.restore_domain.take() {
+            if let Some(idx) = state
+                .current_domains
+                .items
+                .iter()
+                .position(|d| *d == domain)
+            {
+                state.current_domains.status.select(Some(idx));
+            }
+        }
 
-            let domain_list = add_highlight(create_list(
-                domain_items,
-                "Domains".to_string(),
-                Borders::NONE,
-            ));
+        // Decoding only applies to the displayed text, the raw hosts
+        // in `items` are used for lookups and deletions
+        let domain_labels =
+            state.jump_input.is_some() && state.selection == Selection::Domains;
+        let domain_names: Vec<String> = if state.decode_idn {
+            state
+                .current_domains
+                .items
+                .iter()
+                .map(|d| idna::domain_to_unicode(d).0)
+                .collect()
+        } else {
+            state.current_domains.items.clone()
+        };
+        // The number of hosts and cookies of each site is shown
+        // with --flatten
+        let mut sites: HashMap<&str, (HashSet<&str>, usize)> = HashMap::new();
+        if state.flatten {
+            for c in db_indices
+                .iter()
+                .flat_map(|i| cookie_dbs[*i].cookies.iter())
+            {
+                let entry = sites.entry(c.site()).or_default();
+                entry.0.insert(&c.host);
+                entry.1 += 1;
+            }
+        }
+        // Pinned domains are marked with a suffix
+        let suffixes = state
+            .current_domains
+            .items
+            .iter()
+            .map(|d| {
+                let mut spans = vec![];
+                if let Some((hosts, count)) = sites.get(d.as_str()) {
+                    spans.push(Span::styled(
+                        format!(
+                            " [{} host(s), {count} cookie(s)]",
+                            hosts.len()
+                        ),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                if state.pinned.contains(d) {
+                    spans.push(Span::styled(
+                        " [pinned]",
+                        Style::default().fg(Color::LightYellow),
+                    ));
+                }
+                spans
+            })
+            .collect();
+        let domain_items = create_list_items_with_suffix(
+            &domain_names,
+            domain_labels,
+            suffixes,
+        );
 
-            //== Render domains ==//
-            frame.render_stateful_widget(
-                domain_list,
-                chunks[domains_idx],
-                &mut state.current_domains.status,
+        let title = if state.flatten { "Sites" } else { "Domains" };
+        let domain_list = add_highlight(create_list(
+            domain_items,
+            filter_title(title, &state.domains_filter),
+            Borders::NONE,
+        ));
+
+        // A single profile that failed to load shows the reason instead
+        // of an empty list
+        let load_error = match db_indices.as_slice() {
+            [i] => state.load_errors.get(&cookie_dbs[*i].path),
+            _ => None,
+        };
+
+        //== Render domains ==//
+        if domains_idx != NO_SELECTION {
+            if let Some(e) = load_error {
+                let error =
+                    Paragraph::new(sanitize(&format!("Failed to load: {e}")))
+                        .block(Block::default().title("Domains"))
+                        .style(Style::default().fg(Color::LightRed))
+                        .wrap(Wrap { trim: true });
+                frame.render_widget(error, chunks[domains_idx]);
+            } else {
+                frame.render_stateful_widget(
+                    domain_list,
+                    chunks[domains_idx],
+                    &mut state.current_domains.status,
+                );
+            }
+        }
+
+        //== Cookies ==//
+        if let Some(current_domain) = state.selected_domain() {
+            let (scope, query) = SearchScope::from_query(
+                &state.cookies_filter,
+                state.search_scope,
             );
+            let name_matches =
+                indexed_matches(cookie_dbs, &db_indices, query, |c| {
+                    &c.name_index
+                });
+            // Each cookie is paired with the index of its database
+            let mut cookies: Vec<(&Cookie, usize)> = db_indices
+                .iter()
+                .flat_map(|i| {
+                    cookie_dbs[*i]
+                        .cookies_matching(|c| {
+                            state.domain_entry(c) == current_domain
+                        })
+                        .into_iter()
+                        .map(move |c| (c, *i))
+                })
+                .filter(|(c, _)| {
+                    cookie_matches(c, scope, query, &name_matches)
+                        && cookie_visible(state, c)
+                })
+                .collect();
+            if state.sort_order == SortOrder::LastAccess {
+                cookies.sort_by_key(|(c, _)| cmp::Reverse(c.last_access));
+            }
 
-            //== Cookies ==//
-            if let Some(current_domain) = state.selected_domain() {
-                // Fill the current_cookies state list
-                state.current_cookies.items = cdb
-                    .cookies_for_domain(&current_domain)
-                    .iter()
-                    .map(|c| c.name.to_owned())
-                    .collect();
+            // Fill the current_cookies state list
+            state.current_cookies.items =
+                cookies.iter().map(|(c, _)| c.name.to_owned()).collect();
+            state.current_cookie_dbs =
+                cookies.iter().map(|(_, i)| *i).collect();
+            state.current_cookie_rowids =
+                cookies.iter().map(|(c, _)| c.rowid).collect();
+            state.current_cookies.clamp_selection();
 
-                let cookies_items =
-                    create_list_items(&state.current_cookies.items);
+            // Keep the same cookie selected after new profiles were added
+            if let Some((path, rowid)) = state.restore_cookie.take() {
+                if let Some(idx) = cookies.iter().position(|(c, i)| {
+                    c.rowid == rowid && cookie_dbs[*i].path == path
+                }) {
+                    state.current_cookies.status.select(Some(idx));
+                }
+            }
 
-                let cookies_list = add_highlight(create_list(
-                    cookies_items,
-                    "Cookies".to_string(),
-                    Borders::NONE,
-                ));
+            // The Firefox container of each cookie is shown as a suffix,
+            // together with the host of the cookie for a site
+            let cookie_names: Vec<String> = cookies
+                .iter()
+                .map(|(c, i)| {
+                    let name = match cookie_dbs[*i].container_name(c) {
+                        Some(container) => {
+                            format!("{} [{}]", c.name, container)
+                        }
+                        None => c.name.to_owned(),
+                    };
+                    if state.flatten {
+                        format!("{name} ({})", c.host.trim_start_matches('.'))
+                    } else {
+                        name
+                    }
+                })
+                .collect();
 
-                //== Render cookies ==//
+            // Cookies with a security prefix are highlighted and the
+            // flags of each cookie are shown in a badge, followed by
+            // a mark from `SelectAll`
+            let suffixes = cookies
+                .iter()
+                .map(|(c, i)| {
+                    let mut spans = flag_badge(c);
+                    if !state.marked.is_empty()
+                        && state
+                            .marked
+                            .contains(&(cookie_dbs[*i].path.clone(), c.rowid))
+                    {
+                        spans.push(Span::styled(
+                            " [marked]",
+                            Style::default().fg(Color::LightRed),
+                        ));
+                    }
+                    spans
+                })
+                .collect();
+            let cookies_items: Vec<ListItem> = create_list_items_with_suffix(
+                &cookie_names,
+                state.jump_input.is_some()
+                    && state.selection == Selection::Cookies,
+                suffixes,
+            )
+            .into_iter()
+            .zip(cookies.iter())
+            .map(|(item, (c, _))| {
+                if c.name_prefix().is_some() {
+                    item.style(Style::default().fg(Color::Green))
+                } else {
+                    item
+                }
+            })
+            .collect();
+
+            let cookies_list = add_highlight(create_list(
+                cookies_items,
+                filter_title("Cookies", &state.cookies_filter),
+                Borders::NONE,
+            ));
+
+            //== Render cookies ==//
+            if cookies_idx != NO_SELECTION {
                 frame.render_stateful_widget(
                     cookies_list,
                     chunks[cookies_idx],
                     &mut state.current_cookies.status,
                 );
+            }
 
-                //== Fields ==//
-                //debug_log(format!("Crash {:?} {:?} ",
-                //        state.current_cookies.items,
-                //        state.current_cookies.status.selected()
-                //));
-                if let Some(current_cookie) = state.selected_cookie() {
-                    if let Some(cookie) =
-                        cdb.cookie_for_domain(&current_cookie, &current_domain)
-                    {
-                        // Fill the current_fields state list
-                        state.current_fields.items = vec![
-                            cookie.match_field("Value", true, false),
-                            cookie.match_field("Path", true, false),
-                            cookie.match_field("Creation", true, false),
-                            cookie.match_field("Expiry", true, false),
-                            cookie.match_field("LastAccess", true, false),
-                            cookie.match_field("HttpOnly", true, false),
-                            cookie.match_field("Secure", true, false),
-                            cookie.match_field("SameSite", true, false),
-                        ];
-
-                        // Create list items for the UI
-                        let fields_items: Vec<ListItem> =
-                            create_list_items(&state.current_fields.items);
-
-                        let fields_list = create_list(
-                            fields_items,
-                            "Fields".to_string(),
-                            Borders::ALL,
-                        );
+            //== Fields ==//
+            if let Some((cdb, cookie)) = selected_cookie(state, cookie_dbs) {
+                // Fill the current_fields state list
+                state.current_fields.items = vec![
+                    cookie.match_field("Value", true, false),
+                    cookie.match_field("Path", true, false),
+                    cookie.match_field("Creation", true, false),
+                    if cookie.is_session() {
+                        "Expiry: Session".to_string()
+                    } else {
+                        cookie.match_field("Expiry", true, false)
+                    },
+                    cookie.match_field("LastAccess", true, false),
+                    cookie.match_field("HttpOnly", true, false),
+                    cookie.match_field("Secure", true, false),
+                    cookie.match_field("SameSite", true, false),
+                ];
+                if let Some(priority) = cookie.priority_str() {
+                    state
+                        .current_fields
+                        .items
+                        .push(format!("Priority: {priority}"));
+                }
+                if let Some(scheme) = cookie.source_scheme_str() {
+                    state
+                        .current_fields
+                        .items
+                        .push(format!("SourceScheme: {scheme}"));
+                }
+                if let Some(port) = cookie.source_port {
+                    state
+                        .current_fields
+                        .items
+                        .push(format!("SourcePort: {port}"));
+                }
+                if let Some(container) = cdb.container_name(cookie) {
+                    state
+                        .current_fields
+                        .items
+                        .push(format!("Container: {container}"));
+                }
+                if let Some(prefix) = cookie.name_prefix() {
+                    let violations = cookie.prefix_violations();
+                    state.current_fields.items.push(if violations.is_empty() {
+                        format!("Prefix: {prefix} (valid)")
+                    } else {
+                        format!(
+                            "Prefix: {prefix} (invalid: {})",
+                            violations.join(", ")
+                        )
+                    });
+                }
+                // Cookies can originate from any profile
+                // in the "All profiles" view
+                if db_indices.len() > 1 {
+                    state
+                        .current_fields
+                        .items
+                        .push(format!("Profile: {}", cdb.path_short()));
+                }
+
+                // Create list items for the UI
+                let fields_items: Vec<ListItem> =
+                    create_list_items(&state.current_fields.items, false);
+
+                let fields_list = create_list(
+                    fields_items,
+                    "Fields".to_string(),
+                    Borders::ALL,
+                );
 
-                        if fields_idx != NO_SELECTION {
-                            //== Render fields ==//
-                            frame.render_stateful_widget(
-                                fields_list,
-                                chunks[fields_idx],
-                                &mut state.current_fields.status,
-                            );
-                            if state.current_fields.items.len() > 0 {
-                                state.current_fields.status.select(Some(0));
-                            }
+                if fields_idx != NO_SELECTION {
+                    //== Render fields ==//
+                    frame.render_stateful_widget(
+                        fields_list,
+                        chunks[fields_idx],
+                        &mut state.current_fields.status,
+                    );
+                }
+                if state.selection == Selection::Cookies {
+                    // Keep the selected field across ticks unless it is
+                    // out of range for the current cookie
+                    let len = state.current_fields.items.len();
+                    match state.current_fields.status.selected() {
+                        _ if len == 0 => {}
+                        Some(idx) if idx < len => {}
+                        _ if state.auto_select || state.fields_focused => {
+                            state.current_fields.status.select(Some(0))
                         }
+                        _ => state.current_fields.status.select(None),
                     }
                 }
             }
         }
     }
+
+    if state.popup_open {
+        render_popup(frame, state, cookie_dbs);
+    }
 }
 
 fn handle_search_key(
     code: KeyCode,
     state: &mut State,
-    cookie_dbs: &Vec<CookieDB>,
+    cookie_dbs: &[CookieDB],
 ) {
     match code {
         KeyCode::Enter => {
             state.search_open = false;
-            state.search_matches.clear();
+            state.invalidate_search();
             let query: String = state.search_field.drain(..).collect();
 
             match state.selection {
                 Selection::Profiles => {
-                    // Save all partial matches
-                    for (i, p) in cookie_dbs.iter().enumerate() {
-                        if p.path.to_string_lossy().contains(&query) {
-                            state.search_matches.push(i);
-                        }
-                    }
-                    debug_log(format!(
-                        "Search matches: {:?}",
-                        state.search_matches
-                    ));
-                    // Move selection to the first match (if any)
-                    if state.search_matches.len() > 0 {
-                        state.selected_match = 0;
-                        state.profiles.status.select(Some(
-                            *state.search_matches.get(0).unwrap(),
-                        ));
-                    }
+                    search_profiles(state, cookie_dbs, &query)
                 }
                 Selection::Domains => {
+                    let matches = domain_entry_matches(
+                        state,
+                        cookie_dbs,
+                        &state.selected_db_indices(),
+                        &query,
+                    );
                     if set_matches(
                         &state.current_domains.items,
-                        query,
+                        matches,
                         &mut state.search_matches,
                     ) {
                         state.selected_match = 0;
                         state.current_domains.status.select(Some(
-                            *state.search_matches.get(0).unwrap(),
+                            *state.search_matches.first().unwrap(),
                         ));
                     }
                 }
                 Selection::Cookies => {
-                    if set_matches(
-                        &state.current_cookies.items,
-                        query,
-                        &mut state.search_matches,
-                    ) {
+                    let (scope, query) =
+                        SearchScope::from_query(&query, state.search_scope);
+                    if set_cookie_matches(state, cookie_dbs, scope, query) {
                         state.selected_match = 0;
                         state.current_cookies.status.select(Some(
-                            *state.search_matches.get(0).unwrap(),
+                            *state.search_matches.first().unwrap(),
                         ));
                     }
                 }
@@ -325,183 +798,1160 @@ fn handle_search_key(
         }
         KeyCode::Char(c) => {
             state.search_field.push(c);
+            state.update_filter();
         }
         KeyCode::Backspace => {
             state.search_field.pop();
+            state.update_filter();
         }
         KeyCode::Esc => {
             state.search_field.drain(..);
+            state.clear_filter();
             state.search_open = false
         }
         _ => {}
     }
 }
 
-/// Handle keyboard input
-fn handle_key(
+/// Read the path for an export of the current view,
+/// the search filters are not affected by the input
+fn handle_export_key(
     code: KeyCode,
     state: &mut State,
-    cookie_dbs: &mut Vec<CookieDB>,
+    cookie_dbs: &[CookieDB],
 ) {
     match code {
-        //== Deselect the current split ==//
-        KeyCode::Left | KeyCode::Char('h') => match state.selection {
-            Selection::Profiles => {}
-            Selection::Domains => {
-                state.current_domains.status.select(None);
-                state.search_matches.clear();
-                state.selection = Selection::Profiles;
-            }
-            Selection::Cookies => {
-                state.current_cookies.status.select(None);
-                state.search_matches.clear();
-                state.selection = Selection::Domains;
+        KeyCode::Enter => {
+            let path: String = state.search_field.drain(..).collect();
+            state.search_open = false;
+            state.export_prompt = false;
+            if path.is_empty() {
+                return;
             }
-        },
-        //== Go to next item in split ==//
+            let format = ExportFormat::from_path(&path);
+            let cookies = visible_cookies(state, cookie_dbs);
+            let result = output_writer(&path, false).and_then(|mut out| {
+                out.write_all(format_cookies(&cookies, format).as_bytes())?;
+                Ok(out.flush()?)
+            });
+            state.set_status(match result {
+                Ok(()) => format!(
+                    "Exported {} cookie(s) to {} ({:?})",
+                    cookies.len(),
+                    path,
+                    format
+                ),
+                Err(e) => format!("Export failed: {e}"),
+            });
+        }
+        KeyCode::Char(c) => state.search_field.push(c),
+        KeyCode::Backspace => {
+            state.search_field.pop();
+        }
+        KeyCode::Esc => {
+            state.search_field.drain(..);
+            state.search_open = false;
+            state.export_prompt = false;
+        }
+        _ => {}
+    }
+}
+
+/// The cookies shown in the current view, or only the marked cookies
+/// if there are any
+fn visible_cookies(state: &State, cookie_dbs: &[CookieDB]) -> Vec<Cookie> {
+    visible_rows(state, cookie_dbs)
+        .into_iter()
+        .filter(|(i, rowid)| {
+            state.marked.is_empty()
+                || state
+                    .marked
+                    .contains(&(cookie_dbs[*i].path.clone(), *rowid))
+        })
+        .filter_map(|(i, rowid)| cookie_dbs[i].cookie_by_rowid(rowid).cloned())
+        .collect()
+}
+
+/// The index in `cookie_dbs` and rowid of each cookie in the current view.
+/// In the `Cookies` split, these are the listed cookies (or the search
+/// matches), otherwise the cookies of every listed domain (or matched
+/// domain) that pass the session and empty value filters.
+fn visible_rows(state: &State, cookie_dbs: &[CookieDB]) -> Vec<(usize, i64)> {
+    let searching = !state.search_matches.is_empty();
+    match state.selection {
+        Selection::Cookies => state
+            .current_cookie_dbs
+            .iter()
+            .zip(state.current_cookie_rowids.iter())
+            .enumerate()
+            .filter(|(i, _)| !searching || state.search_matches.contains(i))
+            .map(|(_, (db, rowid))| (*db, *rowid))
+            .collect(),
+        _ => {
+            let domains: HashSet<&str> = state
+                .current_domains
+                .items
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| {
+                    state.selection != Selection::Domains
+                        || !searching
+                        || state.search_matches.contains(i)
+                })
+                .map(|(_, d)| d.as_str())
+                .collect();
+            state
+                .selected_db_indices()
+                .iter()
+                .flat_map(|i| {
+                    cookie_dbs[*i]
+                        .cookies_matching(|c| {
+                            domains.contains(state.domain_entry(c))
+                                && cookie_visible(state, c)
+                        })
+                        .into_iter()
+                        .map(move |c| (*i, c.rowid))
+                })
+                .collect()
+        }
+    }
+}
+
+/// Cookies hidden by the session or empty value filters are
+/// not listed in the `Cookies` split
+fn cookie_visible(state: &State, cookie: &Cookie) -> bool {
+    state.session_filter.matches(cookie)
+        && !(state.hide_empty && cookie.value.is_empty())
+}
+
+/// Scroll or close the value popup
+fn handle_popup_key(code: KeyCode, state: &mut State, cookie_dbs: &[CookieDB]) {
+    let (y, x) = state.popup_scroll;
+    match code {
+        KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => {
+            state.popup_open = false;
+            state.popup_scroll = (0, 0);
+        }
         KeyCode::Down | KeyCode::Char('j') => {
-            match state.selection {
-                Selection::Profiles => state.profiles.next(),
-                Selection::Domains => state.current_domains.next(),
-                Selection::Cookies => {
-                    // Cycle through cookies when the field
-                    // window is selected
-                    state.current_cookies.next()
-                }
-            }
+            state.popup_scroll = (y.saturating_add(1), x)
         }
-        //== Go to previous item in split ==//
         KeyCode::Up | KeyCode::Char('k') => {
-            match state.selection {
-                Selection::Profiles => state.profiles.previous(),
-                Selection::Domains => state.current_domains.previous(),
-                Selection::Cookies => {
-                    // Cycle through cookies when the field
-                    // window is selected
-                    state.current_cookies.previous()
-                }
-            }
+            state.popup_scroll = (y.saturating_sub(1), x)
         }
-        //== Select the next split ==//
-        KeyCode::Right | KeyCode::Char('l') => {
-            match state.selection {
-                Selection::Profiles => {
-                    if state.current_domains.items.len() > 0 {
-                        state.current_domains.status.select(Some(0));
-                        state.search_matches.clear();
-                        state.selected_match = NO_SELECTION;
-                        state.selection = Selection::Domains;
-                    }
-                }
-                Selection::Domains => {
-                    if state.current_cookies.items.len() > 0 {
-                        state.current_cookies.status.select(Some(0));
-                        state.search_matches.clear();
-                        state.selected_match = NO_SELECTION;
-                        state.selection = Selection::Cookies;
-                    }
-                }
-                Selection::Cookies => {
-                    // The `state.current_fields.items` array is empty
-                    // until the next ui() tick.
-                    // This branch is needed to make the `match` exhaustive.
-                }
-            }
+        KeyCode::PageDown => {
+            state.popup_scroll = (y.saturating_add(TUI_POPUP_PAGE), x)
         }
-        //== Select field through search ==//
-        KeyCode::Char('/') => state.search_open = true,
-        //== Go to next match (if any) ==//
-        KeyCode::Char('n') => {
-            if state.search_matches.len() > 0 {
-                // Wrap around if the last match has been reached
-                state.selected_match =
-                    if state.selected_match != state.search_matches.len() - 1 {
-                        state.selected_match + 1
-                    } else {
-                        0
-                    };
-                select_match_in_current_split(state)
-            }
+        KeyCode::PageUp => {
+            state.popup_scroll = (y.saturating_sub(TUI_POPUP_PAGE), x)
         }
-        //== Go to previous match (if any) ==//
-        KeyCode::Char('N') => {
-            if state.search_matches.len() > 0 {
-                // Wrap around if the first match has been reached
-                state.selected_match = if state.selected_match != 0 {
-                    state.selected_match - 1
-                } else {
-                    state.search_matches.len() - 1
-                };
-                select_match_in_current_split(state)
-            }
+        KeyCode::Right | KeyCode::Char('l') if !state.popup_wrap => {
+            state.popup_scroll = (y, x.saturating_add(1))
         }
-        //== Delete cookie(s) ==//
-        KeyCode::Char('D') => {
-            // Clear searches since any previously saved indices
-            // will become incorrect
-            state.search_matches.clear();
-            state.selected_match = NO_SELECTION;
-
-            if let Some(profile_idx) = state.profiles.status.selected() {
-                if let Some(cdb) = cookie_dbs.get_mut(profile_idx) {
-                    if let Some(current_domain) = state.selected_domain() {
-                        delete_in_current_split(state, cdb, current_domain)
-                    }
-                }
-            }
+        KeyCode::Left | KeyCode::Char('h') if !state.popup_wrap => {
+            state.popup_scroll = (y, x.saturating_sub(1))
         }
-        //== Copy value to clipboard ==//
-        KeyCode::Char('C') => {
-            match state.selection {
-                Selection::Profiles => {
-                    // Copy the path to the current profile
-                    copy_to_clipboard(state.selected_profile().unwrap())
-                        .expect("Clipboard copy failed");
-                }
-                Selection::Domains => {
-                    // Copy the name of the current domain
-                    copy_to_clipboard(state.selected_domain().unwrap())
-                        .expect("Clipboard copy failed");
-                }
-                Selection::Cookies => {
-                    // Copy the 'Value' field of the current cookie
-                    let value = state
-                        .current_fields
-                        .items
-                        .first()
-                        .unwrap()
-                        .strip_prefix("Value: ");
-                    copy_to_clipboard(value.unwrap().to_string())
-                        .expect("Clipboard copy failed");
+        KeyCode::Char('w') => {
+            state.popup_wrap = !state.popup_wrap;
+            state.popup_scroll = (0, 0);
+        }
+        KeyCode::Char('d') => {
+            state.popup_decode = !state.popup_decode;
+            state.popup_scroll = (0, 0);
+        }
+        // Other copy keys always use the raw value
+        KeyCode::Char('c') => {
+            let decoded = selected_cookie(state, cookie_dbs)
+                .and_then(|(_, cookie)| decode(&cookie.value));
+            match decoded {
+                Some(d) => {
+                    let status = format!("Copied value ({})", d.label());
+                    copy_text(state, &d.text, status)
                 }
+                None => state.set_status("No encoding detected".to_string()),
             }
         }
         _ => {}
     }
 }
 
-/// Delete the currently selected cookie if in the `Cookies` split
-/// and all cookies from a domain if inside the `Domains` split
-/// To update the internal cookie_db requires a mutable reference
-fn delete_in_current_split(
+/// Order the profiles according to --profile-order
+fn sort_profiles(cookie_dbs: &mut [CookieDB]) {
+    cookie_dbs.sort();
+    if Config::global().profile_order == ProfileOrder::Modified {
+        cookie_dbs.sort_by_key(|c| cmp::Reverse(c.modified));
+    }
+}
+
+/// Add the profiles found by the background scan, duplicates are skipped
+/// and failures are shown in the footer. The selected profile, domain and
+/// cookie stay selected even if their position in a list changes.
+fn add_profiles(
     state: &mut State,
-    cdb: &mut CookieDB,
-    current_domain: String,
+    cookie_dbs: &mut Vec<CookieDB>,
+    found: Vec<ScanResult>,
 ) {
-    match state.selection {
-        // Remove all cookies from the current domain
-        Selection::Domains => {
-            debug_log(format!("Deleting: {current_domain}"));
-            cdb.delete_from_domain(&current_domain, "")
-                .expect("Failed to delete cookies from domain");
+    update_profiles(state, cookie_dbs, |state, cookie_dbs| {
+        for (mut cookie_db, error) in found {
+            if cookie_dbs.contains(&cookie_db) {
+                continue;
+            }
+            if let Some(e) = error {
+                let msg =
+                    format!("Failed to load {}: {e}", cookie_db.path_short());
+                debug_log(&msg);
+                state.set_status(msg);
+                state.load_errors.insert(cookie_db.path.clone(), e);
+            }
+            cookie_db.sort_cookies(state.sort_order);
+            cookie_dbs.push(cookie_db);
+        }
+    })
+}
 
-            // If the removed item was the last domain,
-            // unselect the domains split
-            // The length will be non-decremented until the next `ui()` tick
-            if state.current_domains.items.len() == 1 {
-                state.current_domains.status.select(None)
-            } else if let Some(sel) = state.current_domains.status.selected() {
-                // The selected() index needs to be decremented
+/// Save the selected domain and cookie so that they can be selected again
+/// once the lists have been rebuilt
+fn remember_selection(state: &mut State, cookie_dbs: &[CookieDB]) {
+    state.restore_domain = state.selected_domain();
+    state.restore_cookie = state
+        .selected_cookie_db()
+        .zip(state.selected_cookie_rowid())
+        .map(|(i, rowid)| (cookie_dbs[i].path.clone(), rowid));
+}
+
+/// Add or remove profiles with `update` and rebuild the profiles list.
+/// The selected profile, domain and cookie stay selected even if their
+/// position in a list changes, the profiles list is selected if the
+/// selected profile was removed.
+fn update_profiles<F: FnOnce(&mut State, &mut Vec<CookieDB>)>(
+    state: &mut State,
+    cookie_dbs: &mut Vec<CookieDB>,
+    update: F,
+) {
+    let profile = state.selected_profile();
+    remember_selection(state, cookie_dbs);
+    update(state, cookie_dbs);
+    sort_profiles(cookie_dbs);
+    state.set_profiles(cookie_dbs);
+
+    // Matches are indices in lists that may have changed
+    state.invalidate_search();
+    if state.selection == Selection::Profiles {
+        if let Some(query) = state.profile_query.clone() {
+            search_profiles(state, cookie_dbs, &query);
+        }
+    }
+    if let Some(idx) = profile
+        .as_ref()
+        .and_then(|p| state.profiles.items.iter().position(|i| i == p))
+    {
+        state.profiles.status.select(Some(idx));
+    } else if profile.is_some() {
+        // The selected profile was removed
+        state.selection = Selection::Profiles;
+        state.current_domains.status.select(None);
+        state.current_cookies.status.select(None);
+        state.fields_focused = false;
+        let first = (state.auto_select && !state.profiles.items.is_empty())
+            .then_some(0);
+        state.profiles.status.select(first);
+    } else if state.auto_select && state.profiles.status.selected().is_none() {
+        state.profiles.status.select(Some(0));
+    }
+}
+
+/// Save the indices of all profiles with a path that contains `query` to
+/// `search_matches` and select the first match (if any)
+fn search_profiles(state: &mut State, cookie_dbs: &[CookieDB], query: &str) {
+    state.profile_query = Some(query.to_string());
+    let offset = state.profile_offset();
+    for (i, p) in cookie_dbs.iter().enumerate() {
+        if p.path.to_string_lossy().contains(query) {
+            state.search_matches.push(i + offset);
+        }
+    }
+    debug_log(format!("Search matches: {:?}", state.search_matches));
+    if !state.search_matches.is_empty() {
+        state.selected_match = 0;
+        state
+            .profiles
+            .status
+            .select(Some(*state.search_matches.first().unwrap()));
+    }
+}
+
+/// Perform the pending action if the prompt was answered with 'y'
+fn handle_confirm_key(
+    code: KeyCode,
+    state: &mut State,
+    cookie_dbs: &mut [CookieDB],
+) {
+    let confirmation = state.confirmation.take().unwrap();
+    if code != KeyCode::Char('y') {
+        debug_log("Cancelled pending action");
+        return;
+    }
+    match confirmation.action {
+        PendingAction::DeleteDomains(domains, paths) => {
+            let targets = target_indices(cookie_dbs, &paths);
+            for domain in domains {
+                debug_log(format!("Deleting: {domain}"));
+                for i in targets.iter() {
+                    for host in entry_hosts(state, &cookie_dbs[*i], &domain) {
+                        let result =
+                            cookie_dbs[*i].delete_from_domain(&host, "", None);
+                        report_db_error(
+                            state,
+                            &cookie_dbs[*i],
+                            "delete",
+                            result,
+                        );
+                    }
+                }
+            }
+            // The filtered list only contained matches, restore
+            // the full list of domains
+            state.invalidate_search();
+            state.domains_filter.clear();
+            state.current_domains.status.select(Some(0));
+        }
+        PendingAction::KeepDomain(domain, paths) => {
+            let targets = target_indices(cookie_dbs, &paths);
+            debug_log(format!("Deleting all cookies except: {domain}"));
+            let mut count = 0;
+            let mut failed = false;
+            for i in targets.iter() {
+                let result = cookie_dbs[*i].delete_except_domain(&domain);
+                match report_db_error(state, &cookie_dbs[*i], "delete", result)
+                {
+                    Some(n) => count += n,
+                    None => failed = true,
+                }
+            }
+            // Return to the (reduced) list of domains
+            state.invalidate_search();
+            state.domains_filter.clear();
+            state.cookies_filter.clear();
+            state.current_cookies.status.select(None);
+            state.current_domains.status.select(Some(0));
+            state.selection = Selection::Domains;
+            // Keep the reason of a failure in the footer
+            if !failed {
+                state.set_status(format!("Removed {count} cookie(s)"));
+            }
+        }
+        PendingAction::DeleteMarked(rows) => {
+            debug_log(format!("Deleting {} marked cookie(s)", rows.len()));
+            let mut count = 0;
+            // Includes the rows of profiles that are no longer listed
+            let mut failed = 0;
+            for (path, rowid) in rows {
+                let Some(i) = cookie_dbs.iter().position(|c| c.path == path)
+                else {
+                    debug_log(format!(
+                        "Profile of marked cookie is gone: {}",
+                        path.display()
+                    ));
+                    failed += 1;
+                    continue;
+                };
+                let result = cookie_dbs[i].delete_cookie(rowid);
+                if report_db_error(state, &cookie_dbs[i], "delete", result)
+                    .is_some()
+                {
+                    count += 1;
+                } else {
+                    failed += 1;
+                }
+            }
+            // The marked cookies can include every listed cookie,
+            // return to the domains
+            state.invalidate_search();
+            if state.selection == Selection::Cookies {
+                state.current_cookies.status.select(None);
+                state.selection = Selection::Domains;
+            }
+            if failed == 0 {
+                state.set_status(format!("Removed {count} cookie(s)"));
+            } else {
+                state.set_status(format!(
+                    "Removed {count} cookie(s), failed to remove {failed}"
+                ));
+            }
+        }
+    }
+}
+
+/// Handle keyboard input in quick-jump mode, the row with the typed label
+/// is selected once a full label has been typed. Esc or any key that is
+/// not part of a label cancels the mode.
+fn handle_jump_key(code: KeyCode, state: &mut State) {
+    let mut input = state.jump_input.take().unwrap_or_default();
+    let KeyCode::Char(c) = code else {
+        return;
+    };
+    input.push(c);
+
+    let count = state.current_list().items.len();
+    if let Some(idx) = (0..count).find(|i| jump_label(*i, count) == input) {
+        state.current_list_mut().status.select(Some(idx));
+    } else if (0..count).any(|i| jump_label(i, count).starts_with(&input)) {
+        state.jump_input = Some(input);
+    }
+}
+
+/// Label of the row at `idx` in quick-jump mode for a list with `count`
+/// rows, every label in a list has the same length
+fn jump_label(idx: usize, count: usize) -> String {
+    let chars: Vec<char> = TUI_JUMP_CHARS.chars().collect();
+    let mut width = 1;
+    let mut capacity = chars.len();
+    while capacity < count {
+        width += 1;
+        capacity *= chars.len();
+    }
+    let mut label = vec![];
+    let mut n = idx;
+    for _ in 0..width {
+        label.push(chars[n % chars.len()]);
+        n /= chars.len();
+    }
+    label.iter().rev().collect()
+}
+
+/// Resolve the action bound to a key in normal mode, digits are buffered
+/// as a count prefix and the first key of a chord is buffered until the
+/// next key is pressed or `TUI_CHORD_TIMEOUT` passes
+fn resolve_key(code: KeyCode, state: &mut State) -> Option<Action> {
+    //== Buffer a count prefix for the next motion ==//
+    if let KeyCode::Char(c @ '0'..='9') = code {
+        // A leading zero is not a count
+        if (c != '0' || !state.count_prefix.is_empty())
+            && state.count_prefix.len() < TUI_COUNT_PREFIX_LIM
+        {
+            state.count_prefix.push(c);
+        }
+        return None;
+    }
+
+    //== Complete a pending chord ==//
+    let timeout = Duration::from_millis(TUI_CHORD_TIMEOUT);
+    if let Some((first, pressed)) = state.pending_key.take() {
+        if pressed.elapsed() < timeout {
+            if let Some(action) = state.keymap.chord_action(first, code) {
+                return Some(action);
+            }
+        }
+        // Keys that do not complete the chord are handled as usual
+    }
+    if state.keymap.starts_chord(code) {
+        state.pending_key = Some((code, Instant::now()));
+        return None;
+    }
+
+    let action = state.keymap.action(code);
+    if action.is_none() {
+        state.count_prefix.clear();
+    }
+    action
+}
+
+/// Handle an action in normal mode
+fn handle_action(
+    action: Action,
+    state: &mut State,
+    cookie_dbs: &mut [CookieDB],
+) {
+    // The count is reset by any action, but only used for motions
+    let count = state.take_count();
+
+    match action {
+        //== Deselect the current split ==//
+        Action::Left => match state.selection {
+            Selection::Profiles => {}
+            Selection::Domains => {
+                state.current_domains.status.select(None);
+                state.invalidate_search();
+                state.domains_filter.clear();
+                state.selection = Selection::Profiles;
+            }
+            Selection::Cookies if state.fields_focused => {
+                // The value is copied from the cookies split
+                state.current_fields.status.select(Some(0));
+                state.fields_focused = false;
+            }
+            Selection::Cookies => {
+                if let (Some(domain), Some(idx)) = (
+                    state.selected_domain(),
+                    state.current_cookies.status.selected(),
+                ) {
+                    state.last_cookie_idx.insert(domain, idx);
+                }
+                state.current_cookies.status.select(None);
+                state.invalidate_search();
+                state.cookies_filter.clear();
+                state.selection = Selection::Domains;
+            }
+        },
+        //== Restore the full list of the current split ==//
+        Action::ClearFilter => {
+            state.invalidate_search();
+            state.clear_filter();
+        }
+        //== Go to next item in split ==//
+        Action::Down => {
+            for _ in 0..count {
+                match state.selection {
+                    Selection::Profiles => state.profiles.next(),
+                    Selection::Domains => state.current_domains.next(),
+                    Selection::Cookies if state.fields_focused => {
+                        state.current_fields.next()
+                    }
+                    Selection::Cookies => state.current_cookies.next(),
+                }
+            }
+        }
+        //== Go to previous item in split ==//
+        Action::Up => {
+            for _ in 0..count {
+                match state.selection {
+                    Selection::Profiles => state.profiles.previous(),
+                    Selection::Domains => state.current_domains.previous(),
+                    Selection::Cookies if state.fields_focused => {
+                        state.current_fields.previous()
+                    }
+                    Selection::Cookies => state.current_cookies.previous(),
+                }
+            }
+        }
+        //== Select the next split ==//
+        Action::Right => {
+            match state.selection {
+                Selection::Profiles => {
+                    if !state.current_domains.items.is_empty() {
+                        state.current_domains.status.select(Some(0));
+                        state.invalidate_search();
+                        state.selection = Selection::Domains;
+                    }
+                }
+                Selection::Domains => {
+                    state.enter_cookies();
+                }
+                Selection::Cookies => {
+                    // The `state.current_fields.items` array is empty
+                    // until the next ui() tick after entering the split
+                    if !state.current_fields.items.is_empty() {
+                        state.current_fields.status.select(Some(0));
+                        state.fields_focused = true;
+                    }
+                }
+            }
+        }
+        //== Move from the domains or cookies split to the fields ==//
+        Action::FocusFields => {
+            let in_cookies = match state.selection {
+                Selection::Profiles => false,
+                Selection::Domains => state.enter_cookies(),
+                Selection::Cookies => true,
+            };
+            if in_cookies {
+                state.current_fields.status.select(Some(0));
+                state.fields_focused = true;
+            }
+        }
+        //== Show the full value of the selected cookie ==//
+        Action::ShowValue if state.selection == Selection::Cookies => {
+            state.popup_open = true;
+            state.popup_scroll = (0, 0);
+        }
+        //== Cycle between showing all, persistent or session cookies ==//
+        Action::ToggleSession => {
+            state.session_filter = state.session_filter.next();
+            state.marked.clear();
+            state.current_cookies.status.select(
+                if state.selection == Selection::Cookies {
+                    Some(0)
+                } else {
+                    None
+                },
+            );
+            if state.selection == Selection::Cookies {
+                state.invalidate_search();
+            }
+        }
+        //== Show or hide cookies without a value ==//
+        Action::ToggleEmpty => {
+            state.hide_empty = !state.hide_empty;
+            state.marked.clear();
+            state.current_cookies.status.select(
+                if state.selection == Selection::Cookies {
+                    Some(0)
+                } else {
+                    None
+                },
+            );
+            if state.selection == Selection::Cookies {
+                state.invalidate_search();
+            }
+        }
+        //== Pin or unpin the selected domain ==//
+        Action::TogglePin if state.selection == Selection::Domains => {
+            if let Some(domain) = state.selected_domain() {
+                let pinned = if state.pinned.remove(&domain) {
+                    "Unpinned"
+                } else {
+                    state.pinned.insert(domain.clone());
+                    "Pinned"
+                };
+                // The domain moves, matches refer to the old order
+                state.invalidate_search();
+                state.set_status(match save_pinned(&state.pinned) {
+                    Ok(()) => format!("{pinned} {domain}"),
+                    Err(e) => format!("Failed to save pinned domains: {e}"),
+                });
+                state.restore_domain = Some(domain);
+            }
+        }
+        //== Label each row of the current split for quick selection ==//
+        Action::QuickJump if !state.current_list().items.is_empty() => {
+            state.jump_input = Some(String::new());
+        }
+        //== Toggle Unicode display of punycode domains ==//
+        Action::ToggleIdn => state.decode_idn = !state.decode_idn,
+        //== Toggle absolute paths in the profiles split ==//
+        Action::TogglePaths => state.full_paths = !state.full_paths,
+        //== Select field through search ==//
+        Action::Search => state.search_open = true,
+        //== Export the cookies of the current view to a file ==//
+        Action::ExportView => {
+            state.export_prompt = true;
+            state.search_open = true;
+        }
+        //== Go to next match (if any) ==//
+        Action::NextMatch if !state.search_matches.is_empty() => {
+            // Wrap around if the last match has been reached
+            state.selected_match =
+                if state.selected_match != state.search_matches.len() - 1 {
+                    state.selected_match + 1
+                } else {
+                    0
+                };
+            select_match_in_current_split(state)
+        }
+        //== Go to previous match (if any) ==//
+        Action::PreviousMatch if !state.search_matches.is_empty() => {
+            // Wrap around if the first match has been reached
+            state.selected_match = if state.selected_match != 0 {
+                state.selected_match - 1
+            } else {
+                state.search_matches.len() - 1
+            };
+            select_match_in_current_split(state)
+        }
+        //== Mark every cookie in the current view, or clear the marks ==//
+        Action::SelectAll if state.selection != Selection::Profiles => {
+            if state.marked.is_empty() {
+                state.marked = visible_rows(state, cookie_dbs)
+                    .into_iter()
+                    .map(|(i, rowid)| (cookie_dbs[i].path.clone(), rowid))
+                    .collect();
+                state.set_status(format!(
+                    "Marked {} cookie(s)",
+                    state.marked.len()
+                ));
+            } else {
+                state.marked.clear();
+                state.set_status("Cleared marked cookies".to_string());
+            }
+        }
+        //== Delete the marked cookies ==//
+        Action::Delete if !state.marked.is_empty() => {
+            let marked: Vec<(PathBuf, i64)> = state
+                .marked
+                .iter()
+                .filter(|(path, _)| {
+                    cookie_dbs.iter().any(|c| &c.path == path && !c.readonly)
+                })
+                .cloned()
+                .collect();
+            if marked.is_empty() {
+                report_readonly(state, "deletion");
+                return;
+            }
+            let targets: Vec<usize> = (0..cookie_dbs.len())
+                .filter(|i| {
+                    marked.iter().any(|(p, _)| &cookie_dbs[*i].path == p)
+                })
+                .collect();
+            if report_running_browser(state, cookie_dbs, &targets) {
+                return;
+            }
+            let dry_run = if Config::global().dry_run {
+                " [dry run]"
+            } else {
+                ""
+            };
+            state.confirmation = Some(Confirmation {
+                prompt: format!(
+                    "Delete {} marked cookie(s)?{dry_run} (y/n)",
+                    marked.len()
+                ),
+                action: PendingAction::DeleteMarked(marked),
+            });
+            if Config::global().yes {
+                handle_confirm_key(KeyCode::Char('y'), state, cookie_dbs);
+            }
+        }
+        //== Delete cookie(s) ==//
+        Action::Delete => {
+            // Clear searches since any previously saved indices
+            // will become incorrect
+            state.invalidate_search();
+
+            let targets = target_dbs(state, cookie_dbs);
+            if targets.is_empty() {
+                report_readonly(state, "deletion");
+                return;
+            }
+            if report_running_browser(state, cookie_dbs, &targets) {
+                return;
+            }
+            if let Some(current_domain) = state.selected_domain() {
+                delete_in_current_split(
+                    state,
+                    cookie_dbs,
+                    &targets,
+                    current_domain,
+                )
+            }
+        }
+        //== Expire cookie(s) ==//
+        Action::Expire => {
+            let targets = target_dbs(state, cookie_dbs);
+            if targets.is_empty() {
+                report_readonly(state, "expiry");
+                return;
+            }
+            if report_running_browser(state, cookie_dbs, &targets) {
+                return;
+            }
+            if let Some(current_domain) = state.selected_domain() {
+                expire_in_current_split(
+                    state,
+                    cookie_dbs,
+                    &targets,
+                    current_domain,
+                )
+            }
+        }
+        //== Delete cookies from all domains matched by a search ==//
+        Action::DeleteMatches => {
+            if state.selection != Selection::Domains
+                || state.search_matches.is_empty()
+            {
+                return;
+            }
+            let targets = target_dbs(state, cookie_dbs);
+            if targets.is_empty() {
+                report_readonly(state, "deletion");
+                return;
+            }
+            if report_running_browser(state, cookie_dbs, &targets) {
+                return;
+            }
+            let domains: Vec<String> = state
+                .search_matches
+                .iter()
+                .filter_map(|i| state.current_domains.items.get(*i))
+                .cloned()
+                .collect();
+            let count: usize = targets
+                .iter()
+                .flat_map(|i| {
+                    domains.iter().map(|d| {
+                        cookie_dbs[*i]
+                            .cookies_matching(|c| state.domain_entry(c) == d)
+                            .len()
+                    })
+                })
+                .sum();
+            let dry_run = if Config::global().dry_run {
+                " [dry run]"
+            } else {
+                ""
+            };
+            state.confirmation = Some(Confirmation {
+                prompt: format!(
+                    "Delete {} cookie(s) from {} domain(s)?{} (y/n)",
+                    count,
+                    domains.len(),
+                    dry_run
+                ),
+                action: PendingAction::DeleteDomains(
+                    domains,
+                    target_paths(cookie_dbs, &targets),
+                ),
+            });
+            if Config::global().yes {
+                handle_confirm_key(KeyCode::Char('y'), state, cookie_dbs);
+            }
+        }
+        //== Delete cookies from every domain except the selected one ==//
+        Action::KeepDomain => {
+            let Some(domain) = state.selected_domain() else {
+                return;
+            };
+            let targets = target_dbs(state, cookie_dbs);
+            if targets.is_empty() {
+                report_readonly(state, "deletion");
+                return;
+            }
+            if report_running_browser(state, cookie_dbs, &targets) {
+                return;
+            }
+            let kept = domain.trim_start_matches('.');
+            let count: usize = targets
+                .iter()
+                .map(|i| {
+                    cookie_dbs[*i]
+                        .cookies_matching(|c| !c.host.contains(kept))
+                        .len()
+                })
+                .sum();
+            let dry_run = if Config::global().dry_run {
+                " [dry run]"
+            } else {
+                ""
+            };
+            state.confirmation = Some(Confirmation {
+                prompt: format!(
+                    "Delete {count} cookie(s) from all domains except \
+                     {kept}?{dry_run} (y/n)",
+                ),
+                action: PendingAction::KeepDomain(
+                    domain,
+                    target_paths(cookie_dbs, &targets),
+                ),
+            });
+            if Config::global().yes {
+                handle_confirm_key(KeyCode::Char('y'), state, cookie_dbs);
+            }
+        }
+        //== Copy value to clipboard ==//
+        Action::Copy => {
+            match state.selection {
+                Selection::Profiles => {
+                    // Copy the full path to the database of the current
+                    // profile, the list only shows a shortened path
+                    if let [i] = state.selected_db_indices()[..] {
+                        let path = cookie_dbs[i].path.to_string_lossy();
+                        copy_text(state, &path, format!("Copied {path}"));
+                    }
+                }
+                Selection::Domains => {
+                    // Copy the name of the current domain
+                    if let Some(domain) = state.selected_domain() {
+                        copy_text(state, &domain, format!("Copied {domain}"));
+                    }
+                }
+                Selection::Cookies => {
+                    // Copy the value of the currently selected field
+                    if let Some(value) = state.selected_field_value() {
+                        copy_text(state, &value, "Copied value".to_string());
+                    }
+                }
+            }
+        }
+        //== Copy every field of the selected cookie, one per line ==//
+        Action::CopyFields
+            if state.selection == Selection::Cookies
+                && !state.current_fields.items.is_empty() =>
+        {
+            let text = state.current_fields.items.join("\n");
+            let status =
+                format!("Copied {} fields", state.current_fields.items.len());
+            copy_text(state, &text, status);
+        }
+        //== Copy the cookies of the current domain as a JSON array ==//
+        Action::CopyJson | Action::CopyJsonAll
+            if state.selection == Selection::Domains =>
+        {
+            if let Some(domain) = state.selected_domain() {
+                let expired = action == Action::CopyJsonAll;
+                copy_domain_json(state, cookie_dbs, &domain, expired);
+            }
+        }
+        //== Copy the current domain without a leading dot ==//
+        Action::CopyDomain if state.selection != Selection::Profiles => {
+            if let Some(domain) = state.selected_domain() {
+                let domain = domain.trim_start_matches('.');
+                copy_text(state, domain, format!("Copied {domain}"));
+            }
+        }
+        //== Reload the cookies of the selected profile(s) ==//
+        Action::Reload => {
+            let indices = state.selected_db_indices();
+            reload_profiles(state, cookie_dbs, &indices)
+        }
+        _ => {}
+    }
+}
+
+/// Copy every cookie of `domain` in the selected profile(s) as a JSON
+/// array, expired cookies are left out unless `expired` is set
+fn copy_domain_json(
+    state: &mut State,
+    cookie_dbs: &[CookieDB],
+    domain: &str,
+    expired: bool,
+) {
+    let now = Utc::now().timestamp();
+    let cookies: Vec<&Cookie> = state
+        .selected_db_indices()
+        .iter()
+        .flat_map(|i| {
+            cookie_dbs[*i].cookies_matching(|c| {
+                state.domain_entry(c) == domain
+                    && (expired || !c.is_expired(now))
+            })
+        })
+        .collect();
+    let json = serde_json::to_string_pretty(&cookies)
+        .expect("Failed to serialise cookies");
+    copy_text(state, &json, format!("Copied {} cookie(s)", cookies.len()));
+}
+
+/// Copy `text` to the clipboard and show `status` in the footer, or why
+/// copying failed
+fn copy_text(state: &mut State, text: &str, status: String) {
+    match state.clipboard.copy(text) {
+        Ok(()) => state.set_status(status),
+        Err(e) => {
+            debug_log(format!("Clipboard copy failed: {e}"));
+            state.set_status(format!("Failed to copy: {e}"));
+        }
+    }
+}
+
+/// Read the cookies of the profiles at `indices` from disk again, the
+/// selected domain and cookie are kept if they still exist
+fn reload_profiles(
+    state: &mut State,
+    cookie_dbs: &mut [CookieDB],
+    indices: &[usize],
+) {
+    if indices.is_empty() {
+        return;
+    }
+    remember_selection(state, cookie_dbs);
+    let mut count = 0;
+    for i in indices.iter() {
+        let cookie_db = &mut cookie_dbs[*i];
+        match cookie_db.load_cookies() {
+            Ok(_) => {
+                cookie_db.sort_cookies(state.sort_order);
+                state.load_errors.remove(&cookie_db.path);
+                count += cookie_db.cookie_count;
+            }
+            Err(e) => {
+                state
+                    .load_errors
+                    .insert(cookie_db.path.clone(), db_error_reason(&e));
+            }
+        }
+    }
+    state.invalidate_search();
+    let failed = indices
+        .iter()
+        .filter(|i| state.load_errors.contains_key(&cookie_dbs[**i].path))
+        .count();
+    if failed > 0 {
+        state.set_status(format!("Failed to reload {failed} profile(s)"));
+    } else {
+        state.set_status(format!(
+            "Reloaded {count} cookie(s) from {} profile(s)",
+            indices.len()
+        ));
+    }
+}
+
+/// Watch the databases of the selected profile(s) and reload the ones
+/// that the browser has written to, see --watch
+fn watch_profiles(
+    state: &mut State,
+    cookie_dbs: &mut [CookieDB],
+    watcher: &mut DbWatcher,
+) {
+    let indices = state.selected_db_indices();
+    let paths: Vec<PathBuf> = indices
+        .iter()
+        .map(|i| cookie_dbs[*i].path.clone())
+        .collect();
+    if let Err(e) = watcher.watch(&paths) {
+        debug_log(format!("Failed to watch {paths:?}: {e}"));
+        state.set_status(format!("Failed to watch profile: {e}"));
+    }
+    let changed = watcher.changed();
+    let indices: Vec<usize> = indices
+        .into_iter()
+        .filter(|i| changed.contains(&cookie_dbs[*i].path))
+        .collect();
+    if !indices.is_empty() {
+        debug_log(format!("Databases changed: {changed:?}"));
+        reload_profiles(state, cookie_dbs, &indices);
+    }
+}
+
+/// Search the file system for profiles again, returns the channel of the
+/// scan unless a scan is already running or the profiles were picked with
+/// --file or --profile
+fn start_rescan(
+    state: &mut State,
+    cookie_dbs: &[CookieDB],
+) -> Option<Receiver<ScanMessage>> {
+    if state.scanning {
+        state.set_status("A scan is already running".to_string());
+        return None;
+    }
+    if !state.rescan_allowed {
+        state.set_status(
+            "Profiles from --file or --profile are not rescanned".to_string(),
+        );
+        return None;
+    }
+    debug_log("Rescanning all profiles");
+    let known: HashSet<PathBuf> =
+        cookie_dbs.iter().map(|c| c.path.clone()).collect();
+    state.rescan = Some(Rescan {
+        known: known.clone(),
+        found: HashSet::new(),
+    });
+    state.scanning = true;
+    Some(spawn_scan(known))
+}
+
+/// Remove the profiles that were not found again by a completed rescan
+/// and report the profiles that were added and removed
+fn finish_rescan(
+    state: &mut State,
+    cookie_dbs: &mut Vec<CookieDB>,
+    rescan: Rescan,
+) {
+    let added: Vec<&PathBuf> = rescan.found.difference(&rescan.known).collect();
+    let removed: Vec<&PathBuf> =
+        rescan.known.difference(&rescan.found).collect();
+    for path in added.iter() {
+        debug_log(format!("Added profile: {}", path.display()));
+    }
+    for path in removed.iter() {
+        debug_log(format!("Removed profile: {}", path.display()));
+        state.load_errors.remove(*path);
+    }
+    if !removed.is_empty() {
+        update_profiles(state, cookie_dbs, |_, cookie_dbs| {
+            cookie_dbs.retain(|c| rescan.found.contains(&c.path))
+        });
+    }
+    state.set_status(format!(
+        "Rescan finished: {} profile(s) added, {} removed",
+        added.len(),
+        removed.len()
+    ));
+}
+
+/// Show why a modification was skipped in the footer
+fn report_readonly(state: &mut State, action: &str) {
+    debug_log(format!("Skipping {action} in read-only profile"));
+    let msg = if Config::global().readonly {
+        "Modifying cookies is disabled with --readonly"
+    } else {
+        "Profile is read-only"
+    };
+    state.set_status(msg.to_string());
+}
+
+/// Indices of the databases affected by a deletion or expiry in the
+/// current split, read-only databases are excluded. In the `Cookies` split,
+/// only the database of the selected cookie is affected.
+fn target_dbs(state: &State, cookie_dbs: &[CookieDB]) -> Vec<usize> {
+    let indices: Vec<usize> = match state.selection {
+        Selection::Cookies => state.selected_cookie_db().into_iter().collect(),
+        _ => state.selected_db_indices(),
+    };
+    indices
+        .into_iter()
+        .filter(|i| !cookie_dbs[*i].readonly)
+        .collect()
+}
+
+/// Show which browser needs to be closed before the databases at `targets`
+/// can be modified, returns false if none of them is running or with
+/// --ignore-running
+fn report_running_browser(
+    state: &mut State,
+    cookie_dbs: &[CookieDB],
+    targets: &[usize],
+) -> bool {
+    if Config::global().ignore_running || Config::global().dry_run {
+        return false;
+    }
+    let types: Vec<DbType> =
+        targets.iter().map(|i| cookie_dbs[*i].typing).collect();
+    match running_browser(&types) {
+        Some(browser) => {
+            state.set_status(format!("{browser} needs to be closed"));
+            true
+        }
+        None => false,
+    }
+}
+
+/// Paths of the databases at `targets`, a pending action keeps the paths
+/// since the selection can change before the prompt is answered
+fn target_paths(cookie_dbs: &[CookieDB], targets: &[usize]) -> Vec<PathBuf> {
+    targets
+        .iter()
+        .map(|i| cookie_dbs[*i].path.clone())
+        .collect()
+}
+
+/// Indices of the databases at `paths`, profiles that were removed since
+/// the paths were saved are skipped
+fn target_indices(cookie_dbs: &[CookieDB], paths: &[PathBuf]) -> Vec<usize> {
+    paths
+        .iter()
+        .filter_map(|p| cookie_dbs.iter().position(|c| &c.path == p))
+        .collect()
+}
+
+/// Delete the currently selected cookie if in the `Cookies` split
+/// and all cookies from a domain if inside the `Domains` split
+/// To update the internal cookie_db requires a mutable reference
+fn delete_in_current_split(
+    state: &mut State,
+    cookie_dbs: &mut [CookieDB],
+    targets: &[usize],
+    current_domain: String,
+) {
+    match state.selection {
+        // Remove all cookies from the current domain
+        Selection::Domains => {
+            debug_log(format!("Deleting: {current_domain}"));
+            for i in targets {
+                for host in entry_hosts(state, &cookie_dbs[*i], &current_domain)
+                {
+                    let result =
+                        cookie_dbs[*i].delete_from_domain(&host, "", None);
+                    report_db_error(state, &cookie_dbs[*i], "delete", result);
+                }
+            }
+
+            // If the removed item was the last domain,
+            // unselect the domains split
+            // The length will be non-decremented until the next `ui()` tick
+            if state.current_domains.items.len() == 1 {
+                state.current_domains.status.select(None)
+            } else if let Some(sel) = state.current_domains.status.selected() {
+                // The selected() index needs to be decremented
                 // in case we removed that last item
                 if sel == state.current_domains.items.len() - 1 {
                     state.current_domains.status.select(Some(sel - 1));
@@ -510,12 +1960,18 @@ fn delete_in_current_split(
         }
         // Remove a specific cookie from the current domain
         Selection::Cookies => {
-            if let Some(current_cookie) = state.selected_cookie() {
+            if let (Some(current_cookie), Some(rowid)) =
+                (state.selected_cookie(), state.selected_cookie_rowid())
+            {
                 debug_log(format!(
-                    "Deleting: {current_domain}.{current_cookie}"
+                    "Deleting: {current_domain}.{current_cookie} ({rowid})"
                 ));
-                cdb.delete_from_domain(&current_domain, &current_cookie)
-                    .expect("Failed to delete cookie");
+                // Only the selected row is removed, i.e. cookies with the
+                // same name from other containers or paths are kept
+                for i in targets {
+                    let result = cookie_dbs[*i].delete_cookie(rowid);
+                    report_db_error(state, &cookie_dbs[*i], "delete", result);
+                }
 
                 // If the removed item was the last cookie,
                 // unselect the cookie split
@@ -541,13 +1997,96 @@ fn delete_in_current_split(
     }
 }
 
+/// Hosts in `cookie_db` that are listed under `entry` in the Domains
+/// split, every host of the site with --flatten
+fn entry_hosts(
+    state: &State,
+    cookie_db: &CookieDB,
+    entry: &str,
+) -> Vec<String> {
+    if !state.flatten {
+        return vec![entry.to_string()];
+    }
+    let hosts: BTreeSet<&str> = cookie_db
+        .cookies
+        .iter()
+        .filter(|c| c.site() == entry)
+        .map(|c| c.host.as_str())
+        .collect();
+    hosts.into_iter().map(String::from).collect()
+}
+
+/// Show a failed database operation in the footer, the TUI keeps running
+/// so that e.g. a browser holding a lock can be closed before a retry
+fn report_db_error<T>(
+    state: &mut State,
+    cookie_db: &CookieDB,
+    action: &str,
+    result: Result<T, DbError>,
+) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            let msg = modify_error_message(action, &cookie_db.path_short(), &e);
+            debug_log(&msg);
+            state.set_status(msg);
+            None
+        }
+    }
+}
+
+/// Expire the currently selected cookie if in the `Cookies` split
+/// and all cookies from a domain if inside the `Domains` split.
+/// Unlike deletions, the cookies remain in the view.
+fn expire_in_current_split(
+    state: &mut State,
+    cookie_dbs: &mut [CookieDB],
+    targets: &[usize],
+    current_domain: String,
+) {
+    match state.selection {
+        Selection::Domains => {
+            debug_log(format!("Expiring: {current_domain}"));
+            for i in targets {
+                for host in entry_hosts(state, &cookie_dbs[*i], &current_domain)
+                {
+                    let result =
+                        cookie_dbs[*i].expire_from_domain(&host, "", None);
+                    report_db_error(state, &cookie_dbs[*i], "expire", result);
+                }
+            }
+        }
+        Selection::Cookies => {
+            if let (Some(current_cookie), Some(rowid)) =
+                (state.selected_cookie(), state.selected_cookie_rowid())
+            {
+                debug_log(format!(
+                    "Expiring: {current_domain}.{current_cookie} ({rowid})"
+                ));
+                for i in targets {
+                    let result = cookie_dbs[*i].expire_cookie(rowid);
+                    report_db_error(state, &cookie_dbs[*i], "expire", result);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
 /// The `selected_match` is an index in the `search_matches` array, the
 /// `search_matches` array contains the indices in the actual list.
+/// Matches that no longer point into the list are discarded.
 fn select_match_in_current_split(state: &mut State) {
-    let list_idx = state
+    let list_len = state.current_list().items.len();
+    let Some(list_idx) = state
         .search_matches
         .get(state.selected_match)
-        .expect("Invalid index specified for `search_matches`");
+        .filter(|i| **i < list_len)
+    else {
+        debug_log("Discarding stale search matches");
+        state.invalidate_search();
+        return;
+    };
 
     debug_log(format!(
         "Selecting match[{}] -> list[{}]",
@@ -565,83 +2104,536 @@ fn select_match_in_current_split(state: &mut State) {
     }
 }
 
-/// Save all partial matches of the query to `search_matches` and
-/// return true if at least one match was found
+/// Save the indices of the `items` that are part of the `matches` of a query
+/// to `search_matches` and return true if at least one match was found
 fn set_matches(
-    items: &Vec<String>,
-    q: String,
+    items: &[String],
+    matches: Option<HashSet<&str>>,
     search_matches: &mut Vec<usize>,
 ) -> bool {
     for (i, p) in items.iter().enumerate() {
-        if p.contains(&q) {
+        if matches.as_ref().is_none_or(|m| m.contains(p.as_str())) {
             search_matches.push(i);
         }
     }
     debug_log(format!("Search matches: {:?}", search_matches));
-    search_matches.len() != 0
+    !search_matches.is_empty()
+}
+
+/// Same as `set_matches()` for the `Cookies` split, the listed names are
+/// not enough to match values so each cookie is looked up from its rowid
+fn set_cookie_matches(
+    state: &mut State,
+    cookie_dbs: &[CookieDB],
+    scope: SearchScope,
+    query: &str,
+) -> bool {
+    let name_matches =
+        indexed_matches(cookie_dbs, &state.selected_db_indices(), query, |c| {
+            &c.name_index
+        });
+    for (i, (db, rowid)) in state
+        .current_cookie_dbs
+        .iter()
+        .zip(state.current_cookie_rowids.iter())
+        .enumerate()
+    {
+        if cookie_dbs[*db]
+            .cookie_by_rowid(*rowid)
+            .is_some_and(|c| cookie_matches(c, scope, query, &name_matches))
+        {
+            state.search_matches.push(i);
+        }
+    }
+    debug_log(format!("Search matches: {:?}", state.search_matches));
+    !state.search_matches.is_empty()
+}
+
+/// Whether a cookie matches a search for `query` within `scope`,
+/// `name_matches` holds the names that contain the query
+fn cookie_matches(
+    cookie: &Cookie,
+    scope: SearchScope,
+    query: &str,
+    name_matches: &Option<HashSet<&str>>,
+) -> bool {
+    let Some(names) = name_matches else {
+        return true;
+    };
+    (scope != SearchScope::Values && names.contains(cookie.name.as_str()))
+        || (scope != SearchScope::Names && cookie.value.contains(query))
 }
 
 //============================================================================//
 
+/// The domains or cookie names in the given databases that contain `query`,
+/// looked up in the search index of each database. Returns `None` for an
+/// empty query, which matches everything.
+fn indexed_matches<'a>(
+    cookie_dbs: &'a [CookieDB],
+    db_indices: &[usize],
+    query: &str,
+    index: fn(&CookieDB) -> &SearchIndex,
+) -> Option<HashSet<&'a str>> {
+    if query.is_empty() {
+        return None;
+    }
+    Some(
+        db_indices
+            .iter()
+            .flat_map(|i| index(&cookie_dbs[*i]).matching(query))
+            .collect(),
+    )
+}
+
+/// The entries of the `Domains` split with a host that contains `query`,
+/// hosts are matched against the index and a site is an entry if any of
+/// its hosts match. Returns `None` for an empty query.
+fn domain_entry_matches<'a>(
+    state: &State,
+    cookie_dbs: &'a [CookieDB],
+    db_indices: &[usize],
+    query: &str,
+) -> Option<HashSet<&'a str>> {
+    indexed_matches(cookie_dbs, db_indices, query, |c| &c.domain_index).map(
+        |m| {
+            db_indices
+                .iter()
+                .flat_map(|i| cookie_dbs[*i].cookies.iter())
+                .filter(|c| m.contains(c.host.as_str()))
+                .map(|c| state.domain_entry(c))
+                .collect()
+        },
+    )
+}
+
 /// Create list items for the UI
 /// Nodes with text exceeding `TUI_TEXT_TRUNCATE_LIM`
 /// will be truncated with `...`
-fn create_list_items<T: ToString>(items: &Vec<T>) -> Vec<ListItem> {
+/// Each item is prefixed with its quick-jump label if `labels` is set
+fn create_list_items<T: ToString>(
+    items: &[T],
+    labels: bool,
+) -> Vec<ListItem<'static>> {
+    create_list_items_with_suffix(items, labels, vec![])
+}
+
+/// Same as `create_list_items()`, with styled spans appended to each item,
+/// items without a corresponding entry in `suffixes` are left as is
+fn create_list_items_with_suffix<T: ToString>(
+    items: &[T],
+    labels: bool,
+    suffixes: Vec<Vec<Span<'static>>>,
+) -> Vec<ListItem<'static>> {
+    let mut suffixes = suffixes.into_iter();
     items
         .iter()
-        .map(|p| {
-            let p: String = p.to_string();
-            let text = if p.len() > TUI_TEXT_TRUNCATE_LIM {
-                format!("{}..", &p[0..TUI_TEXT_TRUNCATE_LIM])
-            } else {
-                p
-            };
-            ListItem::new(text)
+        .enumerate()
+        .map(|(i, p)| {
+            let text =
+                truncate(&sanitize(&p.to_string()), TUI_TEXT_TRUNCATE_LIM);
+            let mut spans = vec![];
+            if labels {
+                spans.push(Span::styled(
+                    format!("{} ", jump_label(i, items.len())),
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(Color::LightYellow)
+                        .add_modifier(Modifier::BOLD),
+                ));
+            }
+            spans.push(Span::raw(text));
+            spans.extend(suffixes.next().unwrap_or_default());
+            ListItem::new(Spans::from(spans))
         })
         .collect()
 }
 
+/// Badge with the security attributes of a cookie, e.g. `[S H L]`:
+/// `S` for Secure, `H` for HttpOnly and `L`, `X` or `N` for a SameSite
+/// policy of Lax, Strict or None
+fn flag_badge(cookie: &Cookie) -> Vec<Span<'static>> {
+    let mut flags = vec![];
+    if cookie.secure {
+        flags.push(("S", Color::LightGreen));
+    }
+    if cookie.http_only {
+        flags.push(("H", Color::LightBlue));
+    }
+    flags.push(match cookie.samesite {
+        2 => ("X", Color::LightMagenta),
+        1 => ("L", Color::LightYellow),
+        _ => ("N", Color::LightRed),
+    });
+
+    let mut spans = vec![Span::raw(" [")];
+    for (i, (flag, color)) in flags.into_iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(" "));
+        }
+        spans.push(Span::styled(flag, Style::default().fg(color)));
+    }
+    spans.push(Span::raw("]"));
+    spans
+}
+
+/// Replace control characters with visible placeholders before rendering,
+/// e.g. a newline is shown as `␊`. Raw control characters can break the
+/// layout or be interpreted as escape sequences by the terminal.
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\u{0}'..='\u{1f}' => {
+                char::from_u32(0x2400 + c as u32).unwrap_or('\u{fffd}')
+            }
+            '\u{7f}' => '\u{2421}',
+            c if c.is_control() => '\u{fffd}',
+            c => c,
+        })
+        .collect()
+}
+
+/// Truncate text that exceeds `lim` characters with `..`.
+/// The limit is counted in characters rather than bytes so that
+/// multibyte characters are never split.
+fn truncate(text: &str, lim: usize) -> String {
+    match text.char_indices().nth(lim) {
+        Some((byte_idx, _)) => format!("{}..", &text[..byte_idx]),
+        None => text.to_string(),
+    }
+}
+
+/// Same as `truncate()` but the start of the text is replaced with `..`
+fn truncate_start(text: &str, lim: usize) -> String {
+    let count = text.chars().count();
+    if count <= lim {
+        return text.to_string();
+    }
+    match text.char_indices().nth(count - lim.saturating_sub(2)) {
+        Some((byte_idx, _)) => format!("..{}", &text[byte_idx..]),
+        None => "..".to_string(),
+    }
+}
+
+/// The currently selected cookie and the database that it belongs to
+fn selected_cookie<'a>(
+    state: &State,
+    cookie_dbs: &'a [CookieDB],
+) -> Option<(&'a CookieDB, &'a Cookie)> {
+    let cdb = cookie_dbs.get(state.selected_cookie_db()?)?;
+    let cookie = cdb.cookie_by_rowid(state.selected_cookie_rowid()?)?;
+    Some((cdb, cookie))
+}
+
+/// Render the full value of the selected cookie in a popup over the
+/// center of the frame. The scroll offset is clamped so that the last
+/// line (or column) of the value can not be scrolled past.
+fn render_popup<B: Backend>(
+    frame: &mut Frame<B>,
+    state: &mut State,
+    cookie_dbs: &[CookieDB],
+) {
+    let Some((_, cookie)) = selected_cookie(state, cookie_dbs) else {
+        return;
+    };
+
+    let area = centered_rect(80, 60, frame.size());
+    // Exclude the borders
+    let width = usize::from(area.width.saturating_sub(2)).max(1);
+    let height = usize::from(area.height.saturating_sub(2));
+
+    // The raw value is shown if it could not be decoded
+    let decoded = if state.popup_decode {
+        decode(&cookie.value)
+    } else {
+        None
+    };
+    let (value, label) = match &decoded {
+        Some(d) => (d.text.as_str(), format!(" [{}]", d.label())),
+        None if state.popup_decode => (cookie.value.as_str(), " [raw]".into()),
+        None => (cookie.value.as_str(), String::new()),
+    };
+
+    // Line breaks in the value are kept, other control characters
+    // are replaced
+    let lines: Vec<String> = value.lines().map(sanitize).collect();
+    let line_lengths: Vec<usize> =
+        lines.iter().map(|l| l.chars().count()).collect();
+    let (max_y, max_x) = if state.popup_wrap {
+        let rows: usize =
+            line_lengths.iter().map(|n| n.div_ceil(width).max(1)).sum();
+        (rows.saturating_sub(height), 0)
+    } else {
+        let cols = line_lengths.iter().max().copied().unwrap_or(0);
+        (
+            line_lengths.len().saturating_sub(height),
+            cols.saturating_sub(width),
+        )
+    };
+    let (y, x) = state.popup_scroll;
+    state.popup_scroll = (
+        y.min(u16::try_from(max_y).unwrap_or(u16::MAX)),
+        x.min(u16::try_from(max_x).unwrap_or(u16::MAX)),
+    );
+
+    let block = Block::default()
+        .border_type(BorderType::Rounded)
+        .borders(Borders::ALL)
+        .title(Span::styled(
+            sanitize(&format!("{}: {}{}", cookie.host, cookie.name, label)),
+            Style::default()
+                .fg(Color::Indexed(TUI_PRIMARY_COLOR))
+                .add_modifier(Modifier::BOLD),
+        ));
+    let mut popup = Paragraph::new(lines.join("\n"))
+        .block(block)
+        .scroll(state.popup_scroll);
+    if state.popup_wrap {
+        popup = popup.wrap(Wrap { trim: false });
+    }
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(popup, area);
+}
+
+/// A rectangle centered in `r` that covers the given percentage
+/// of its width and height
+fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    let vert_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_y) / 2),
+                Constraint::Percentage(percent_y),
+                Constraint::Percentage((100 - percent_y) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(r);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_x) / 2),
+                Constraint::Percentage(percent_x),
+                Constraint::Percentage((100 - percent_x) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(vert_chunks[1])[1]
+}
+
 fn render_search<B: Backend>(
     frame: &mut Frame<B>,
     state: &mut State,
     vert_chunk: Rect,
 ) {
+    let prompt = if state.export_prompt {
+        TUI_EXPORT.to_string()
+    } else if state.selection == Selection::Cookies {
+        let (scope, _) =
+            SearchScope::from_query(&state.search_field, state.search_scope);
+        format!("{} [{}]", TUI_SEARCH, scope.label())
+    } else {
+        TUI_SEARCH.to_string()
+    };
     let input_box =
-        Paragraph::new(format!("{} {}", TUI_SEARCH, state.search_field))
+        Paragraph::new(format!("{} {}", prompt, state.search_field))
             .style(Style::default().fg(Color::Blue));
 
     frame.render_widget(input_box, vert_chunk);
     frame.set_cursor(
         // Put cursor past the end of the input text
         vert_chunk.x
-            + TUI_SEARCH.len() as u16
+            + prompt.len() as u16
             + state.search_field.len() as u16
             + 1,
         vert_chunk.y,
     );
 }
 
-/// Create the usage footer
-fn create_footer() -> Table<'static> {
-    let cells = [
-        Cell::from("/: Search").style(Style::default().fg(Color::LightBlue)),
-        Cell::from("n/N: Next/Previous match"),
-        Cell::from("D: Delete").style(Style::default().fg(Color::LightRed)),
-        Cell::from("C: Copy to clipboard")
-            .style(Style::default().fg(Color::LightYellow)),
-        Cell::from("q: Quit"),
-    ];
-
-    let row = Row::new(cells).bottom_margin(1);
-    Table::new(vec![row])
-        .block(Block::default().borders(Borders::NONE))
-        .widths(&[
-            Constraint::Percentage(7),
-            Constraint::Percentage(15),
-            Constraint::Percentage(7),
-            Constraint::Percentage(12),
-            Constraint::Percentage(7),
-        ])
+/// Create the usage footer with the keys that apply to the selected split,
+/// deletions are unavailable if the current profile is `readonly`
+fn create_footer(state: &State, readonly: bool) -> Paragraph<'static> {
+    let key = |action| state.keymap.key_name(action);
+    let default = Style::default();
+    if state.popup_open {
+        let decode = if state.popup_decode {
+            "Raw value"
+        } else {
+            "Decode"
+        };
+        return footer_line(vec![
+            ("w: Wrap".to_string(), default),
+            (format!("d: {decode}"), default),
+            ("c: Copy decoded".to_string(), default),
+            ("q: Close".to_string(), default),
+        ]);
+    }
+    let red = Style::default().fg(Color::LightRed);
+    let mut controls: Vec<(String, Style)> = vec![];
+
+    if state.selection == Selection::Cookies {
+        controls
+            .push((format!("{}: Show value", key(Action::ShowValue)), default));
+    } else {
+        controls.push((
+            format!("{}: Search", key(Action::Search)),
+            Style::default().fg(Color::LightBlue),
+        ));
+        controls.push((
+            format!(
+                "{}/{}: Next/Previous match",
+                key(Action::NextMatch),
+                key(Action::PreviousMatch)
+            ),
+            default,
+        ));
+    }
+    if state.selection == Selection::Profiles {
+        controls.push((
+            format!(
+                "{}/{}: Reload/Rescan, {}: Full paths",
+                key(Action::Reload),
+                key(Action::Rescan),
+                key(Action::TogglePaths)
+            ),
+            default,
+        ));
+    }
+    // Deletions apply to the selected domain or cookie
+    if state.selection != Selection::Profiles {
+        if readonly {
+            controls.push((
+                "Read-only".to_string(),
+                Style::default().fg(Color::DarkGray),
+            ));
+        } else {
+            let delete = if state.marked.is_empty() {
+                "Delete".to_string()
+            } else {
+                format!("Delete {} marked", state.marked.len())
+            };
+            controls.push((format!("{}: {delete}", key(Action::Delete)), red));
+            controls.push((format!("{}: Expire", key(Action::Expire)), red));
+            if state.selection == Selection::Domains
+                && !state.search_matches.is_empty()
+            {
+                controls.push((
+                    format!("{}: Delete matches", key(Action::DeleteMatches)),
+                    red,
+                ));
+            }
+            if state.selection == Selection::Domains {
+                controls.push((
+                    format!("{}: Keep only domain", key(Action::KeepDomain)),
+                    red,
+                ));
+            }
+        }
+    }
+    if state.selection == Selection::Domains {
+        let pinned = state
+            .selected_domain()
+            .is_some_and(|d| state.pinned.contains(&d));
+        controls.push((
+            format!(
+                "{}: {}",
+                key(Action::TogglePin),
+                if pinned { "Unpin" } else { "Pin" }
+            ),
+            default,
+        ));
+    }
+    let copy_target = match state.selection {
+        Selection::Profiles => "path",
+        Selection::Domains => "domain",
+        Selection::Cookies => "field",
+    };
+    controls.push((
+        format!("{}: Copy {}", key(Action::Copy), copy_target),
+        Style::default().fg(Color::LightYellow),
+    ));
+    if state.selection == Selection::Cookies {
+        controls.push((
+            format!("{}: Copy all fields", key(Action::CopyFields)),
+            Style::default().fg(Color::LightYellow),
+        ));
+    }
+    if state.selection == Selection::Domains {
+        controls.push((
+            format!(
+                "{}/{}: Copy JSON (with expired)",
+                key(Action::CopyJson),
+                key(Action::CopyJsonAll)
+            ),
+            Style::default().fg(Color::LightYellow),
+        ));
+    }
+    if state.selection != Selection::Profiles {
+        controls.push((
+            format!(
+                "{}: {}",
+                key(Action::SelectAll),
+                if state.marked.is_empty() {
+                    "Mark all"
+                } else {
+                    "Clear marks"
+                }
+            ),
+            default,
+        ));
+    }
+    let export = if state.marked.is_empty() {
+        "Export view"
+    } else {
+        "Export marked"
+    };
+    controls.push((format!("{}: {export}", key(Action::ExportView)), default));
+    controls.push((
+        format!(
+            "{}: {}",
+            key(Action::ToggleSession),
+            state.session_filter.label()
+        ),
+        default,
+    ));
+    controls.push((
+        format!(
+            "{}: {}",
+            key(Action::ToggleEmpty),
+            if state.hide_empty {
+                "Empty values hidden"
+            } else {
+                "Empty values shown"
+            }
+        ),
+        default,
+    ));
+    controls.push((format!("{}: Quit", key(Action::Quit)), default));
+    footer_line(controls)
+}
+
+/// Join the hints of the footer into a single line
+fn footer_line(controls: Vec<(String, Style)>) -> Paragraph<'static> {
+    let mut spans = vec![];
+    for (i, (text, style)) in controls.into_iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw("  "));
+        }
+        spans.push(Span::styled(text, style));
+    }
+    Paragraph::new(Spans::from(spans))
+}
+
+/// Create a list title that includes the active filter (if any)
+fn filter_title(title: &str, filter: &str) -> String {
+    if filter.is_empty() {
+        title.to_string()
+    } else {
+        format!("{title} [{filter}]")
+    }
 }
 
 /// Highlighted the currently selected item
@@ -668,15 +2660,145 @@ fn create_list(items: Vec<ListItem>, title: String, border: Borders) -> List {
     )
 }
 
-/// Print a debug message to `DEBUG_LOG`
+/// Print a debug message to the debug log
 fn debug_log<T: std::fmt::Display>(msg: T) {
     if Config::global().debug {
-        let mut f = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(DEBUG_LOG)
-            .expect("Failed to open debug log");
+        write_debug_log("DEBUG", &msg.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clipboard::MockClipboard;
+    use crate::config::{Config, SearchScope, CONFIG, TUI_TEXT_TRUNCATE_LIM};
+    use crate::keymap::{Action, Keymap};
+    use crate::state::{Selection, State};
+    use crate::tui::{
+        handle_action, jump_label, resolve_key, sanitize, truncate,
+        truncate_start,
+    };
+    use crossterm::event::KeyCode;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("github.com", TUI_TEXT_TRUNCATE_LIM), "github.com");
+
+        // Multibyte characters that cross the limit should not panic
+        let domain = "ñ".repeat(TUI_TEXT_TRUNCATE_LIM) + ".example.com";
+        let expected = "ñ".repeat(TUI_TEXT_TRUNCATE_LIM) + "..";
+        assert_eq!(truncate(&domain, TUI_TEXT_TRUNCATE_LIM), expected);
+
+        let domain = "a".repeat(TUI_TEXT_TRUNCATE_LIM - 1) + "日本.jp";
+        let expected = "a".repeat(TUI_TEXT_TRUNCATE_LIM - 1) + "日..";
+        assert_eq!(truncate(&domain, TUI_TEXT_TRUNCATE_LIM), expected);
+
+        assert_eq!(truncate_start("/a/b/Cookies", 12), "/a/b/Cookies");
+        assert_eq!(truncate_start("/a/b/Cookies", 9), "..Cookies");
+        assert_eq!(truncate_start("/a/日本/c", 5), "..本/c");
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("a=b; c"), "a=b; c");
+        assert_eq!(sanitize("line\nbreak\r"), "line\u{240a}break\u{240d}");
+        assert_eq!(sanitize("\x1b[2J\x7f"), "\u{241b}[2J\u{2421}");
+        assert_eq!(sanitize("\u{9b}31m"), "\u{fffd}31m");
+    }
+
+    #[test]
+    fn test_search_scope() {
+        let names = SearchScope::Names;
+        assert_eq!(
+            SearchScope::from_query("v:abc", names),
+            (SearchScope::Values, "abc")
+        );
+        assert_eq!(
+            SearchScope::from_query("b:", names),
+            (SearchScope::Both, "")
+        );
+        assert_eq!(SearchScope::from_query("sid", names), (names, "sid"));
+        // Only a leading prefix selects a scope
+        assert_eq!(SearchScope::from_query("a v:", names), (names, "a v:"));
+    }
 
-        writeln!(f, "-> {msg}").expect("Failed to write debug message");
+    #[test]
+    fn test_jump_label() {
+        assert_eq!(jump_label(0, 5), "a");
+        assert_eq!(jump_label(4, 5), "g");
+        // Two characters are needed for more than 26 rows
+        assert_eq!(jump_label(0, 30), "aa");
+        assert_eq!(jump_label(27, 30), "ss");
+    }
+
+    #[test]
+    fn test_copy_domain() {
+        CONFIG.get_or_init(Config::default);
+        let mut state = State::new(&[], Keymap::default());
+        let clipboard = MockClipboard::default();
+        let copied = clipboard.copied.clone();
+        state.clipboard = Box::new(clipboard);
+
+        state.selection = Selection::Domains;
+        state.current_domains.items = vec![".example.com".to_string()];
+        state.current_domains.status.select(Some(0));
+        handle_action(Action::CopyDomain, &mut state, &mut []);
+
+        // The leading dot is not copied
+        assert_eq!(*copied.borrow(), vec!["example.com".to_string()]);
+    }
+
+    #[test]
+    fn test_stale_search_matches() {
+        CONFIG.get_or_init(Config::default);
+        let mut state = State::new(&[], Keymap::default());
+        state.selection = Selection::Domains;
+        state.current_domains.items = vec![".example.com".to_string()];
+        state.current_domains.status.select(Some(0));
+
+        // Indices from before the list shrunk are discarded on 'n'
+        state.search_matches = vec![0, 3];
+        state.selected_match = 0;
+        handle_action(Action::NextMatch, &mut state, &mut []);
+        assert!(state.search_matches.is_empty());
+        assert_eq!(state.current_domains.status.selected(), Some(0));
+    }
+
+    #[test]
+    fn test_marked_cleared() {
+        CONFIG.get_or_init(Config::default);
+        let mut state = State::new(&[], Keymap::default());
+        state.selection = Selection::Cookies;
+        let row = (PathBuf::from("/p/Cookies"), 1);
+
+        // Marks from the previous view are never operated on
+        state.marked.insert(row.clone());
+        handle_action(Action::ToggleEmpty, &mut state, &mut []);
+        assert!(state.marked.is_empty());
+        state.marked.insert(row.clone());
+        state.search_field = "sid".to_string();
+        state.update_filter();
+        assert!(state.marked.is_empty());
+        state.marked.insert(row);
+        handle_action(Action::SelectAll, &mut state, &mut []);
+        assert!(state.marked.is_empty());
+    }
+
+    #[test]
+    fn test_chord() {
+        CONFIG.get_or_init(Config::default);
+        let keymap = Keymap::parse("delete = dd").unwrap();
+        let mut state = State::new(&[], keymap);
+        let d = KeyCode::Char('d');
+
+        // A single stray press does nothing
+        assert_eq!(resolve_key(d, &mut state), None);
+        assert_eq!(
+            resolve_key(KeyCode::Char('j'), &mut state),
+            Some(Action::Down)
+        );
+        assert_eq!(resolve_key(d, &mut state), None);
+        assert_eq!(resolve_key(d, &mut state), Some(Action::Delete));
+        assert!(state.pending_key.is_none());
     }
 }
@@ -1,27 +1,94 @@
 use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
 use std::{
-    collections::HashSet,
-    env::consts,
+    collections::{HashMap, HashSet},
+    ffi::OsString,
     fs::{File, OpenOptions},
     io::{BufRead, Read, Write},
-    path::Path,
-    process::{Command, Stdio},
+    path::{Path, PathBuf},
 };
 
+use chrono::Utc;
+use serde::Serialize;
 use walkdir::WalkDir;
 
-use sysinfo::{RefreshKind, System, SystemExt};
+use sysinfo::{ProcessExt, RefreshKind, System, SystemExt};
 
-use crate::config::{DB_NAMES, SEARCH_DIRS, SQLITE_FILE_ID};
+use crate::config::{
+    Config, APP_DIR, BROWSER_PROCESSES, CONFIG_DIR, COOKIE_FIELDS, DB_NAMES,
+    FLATPAK_PROCESSES, PINNED_FILE, SEARCH_DIRS, SQLITE_FILE_ID,
+};
 use crate::cookie_db::CookieDB;
+use crate::error::{DbError, Error};
+use crate::{debugln, errln, msg_prefix};
 
 /// The PartialEq trait allows us to use `matches!` to check
 /// equality between enums
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub enum DbType {
     Chrome,
     Firefox,
-    Unknown,
+}
+
+/// Append a message to the debug log, each line is prefixed with
+/// an ISO 8601 timestamp and the `level` of the message
+pub fn write_debug_log(level: &str, msg: &str) {
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(Config::global().log_path())
+        .expect("Failed to open debug log");
+
+    writeln!(
+        f,
+        "{} [{}] {}",
+        Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+        level,
+        msg
+    )
+    .expect("Failed to write debug message");
+}
+
+/// A modification of a cookie database, recorded in the --audit-log
+pub struct AuditEntry<'a> {
+    /// `delete`, `expire`, `clean` or `keep-domain`
+    pub action: &'a str,
+    /// The affected host, `*` if several hosts are affected
+    pub host: &'a str,
+    /// The affected cookie name, `*` for every cookie of the host(s)
+    pub name: &'a str,
+}
+
+/// Append a tab separated line with an ISO 8601 timestamp, the path to
+/// the database and the fields of the `entry` to the audit log at `path`
+pub fn write_audit_log(
+    path: &str,
+    db_path: &Path,
+    entry: &AuditEntry,
+) -> Result<(), io::Error> {
+    let mut f = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(
+        f,
+        "{}\t{}\t{}\t{}\t{}",
+        Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+        db_path.display(),
+        entry.action,
+        entry.host,
+        entry.name
+    )?;
+    f.sync_all()
+}
+
+/// Write a message from `debugln!` or `errln!` to the debug log
+/// if --debug was passed and a log file was configured
+pub fn log_to_file(level: &str, msg: &str) {
+    if Config::global().debug
+        && (Config::global().log_file.is_some() || Config::global().tui)
+    {
+        write_debug_log(level, msg)
+    }
 }
 
 /// Returns /mnt/c/Users/$USER under WSL, otherwise the value of $HOME
@@ -33,8 +100,26 @@ pub fn get_home() -> String {
     }
 }
 
-/// Check if a process is running using the `sysinfo` library
-pub fn process_is_running(name: &str) -> bool {
+/// The directory of rokie inside an XDG base directory, e.g. `CONFIG_DIR`
+pub fn app_dir(base_dir: (&str, &str)) -> PathBuf {
+    let (var, fallback) = base_dir;
+    xdg_dir(std::env::var_os(var), &get_home(), fallback).join(APP_DIR)
+}
+
+/// An XDG base directory from the `value` of its environment variable,
+/// relative paths are ignored as required by the specification and
+/// `fallback` under `home` is used instead
+fn xdg_dir(value: Option<OsString>, home: &str, fallback: &str) -> PathBuf {
+    match value.map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => Path::new(home).join(fallback),
+    }
+}
+
+/// The name of a running browser from `BROWSER_PROCESSES` that uses one
+/// of the given database types (if any), processes are listed with the
+/// `sysinfo` library
+pub fn running_browser(types: &[DbType]) -> Option<&'static str> {
     let sys = System::new_with_specifics(
         RefreshKind::everything()
             .without_cpu()
@@ -44,73 +129,434 @@ pub fn process_is_running(name: &str) -> bool {
             .without_components()
             .without_users_list(),
     );
-    let found = sys
-        .processes_by_exact_name(name)
-        .find_map(|_| Some(true))
-        .is_some();
-    found
+    sys.processes()
+        .values()
+        .filter_map(|p| browser_process(p.name(), p.exe(), p.cmd()))
+        .find(|(_, typing)| types.contains(typing))
+        .map(|(browser, _)| browser)
+}
+
+/// The browser that a process belongs to. The process name is not enough
+/// on its own since it is truncated on Linux and wrappers can start the
+/// browser under another name, the file names of the executable and of
+/// `argv[0]` are matched as well.
+fn browser_process(
+    name: &str,
+    exe: &Path,
+    cmd: &[String],
+) -> Option<(&'static str, DbType)> {
+    let file_names: Vec<String> = [
+        Some(name),
+        exe.file_name().and_then(|n| n.to_str()),
+        cmd.first()
+            .and_then(|arg| Path::new(arg).file_name())
+            .and_then(|n| n.to_str()),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|n| n.to_lowercase().trim_end_matches(".exe").to_string())
+    .collect();
+    let flatpak = FLATPAK_PROCESSES.contains(&name);
+
+    BROWSER_PROCESSES
+        .iter()
+        .find(|(_, _, names, flatpak_id)| {
+            file_names.iter().any(|n| names.contains(&n.as_str()))
+                || (flatpak && cmd.iter().any(|arg| arg.contains(flatpak_id)))
+        })
+        .map(|(browser, typing, _, _)| (*browser, *typing))
+}
+
+/// Check if the current user can write to the given file. SQLite also
+/// needs write access to the parent directory to create journal files.
+pub fn is_writable(filepath: &Path) -> bool {
+    let parent = match filepath.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let parent_writable = parent
+        .metadata()
+        .map(|m| !m.permissions().readonly())
+        .unwrap_or(false);
+
+    parent_writable && OpenOptions::new().write(true).open(filepath).is_ok()
 }
 
+/// Check if any user can modify the given file or replace it through
+/// its parent directory, directories with the sticky bit set (e.g. /tmp)
+/// only allow the owner to replace a file
+#[cfg(unix)]
+pub fn is_world_writable(filepath: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = |p: &Path| p.metadata().map_or(0, |m| m.permissions().mode());
+
+    let parent_mode = filepath.parent().map_or(0, mode);
+    mode(filepath) & 0o002 != 0
+        || (parent_mode & 0o002 != 0 && parent_mode & 0o1000 == 0)
+}
+
+#[cfg(not(unix))]
+pub fn is_world_writable(_filepath: &Path) -> bool {
+    false
+}
+
+/// A temporary copy of a SQLite database together with its `-wal` and
+/// `-shm` files. Reading from a copy avoids locking errors while the
+/// browser is running. The copy is removed when the snapshot is dropped.
+pub struct Snapshot {
+    dir: PathBuf,
+    pub path: PathBuf,
+}
+
+/// Used to give each snapshot a unique directory
+static SNAPSHOT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+impl Snapshot {
+    pub fn new(filepath: &Path) -> Result<Self, io::Error> {
+        let dir = std::env::temp_dir().join(format!(
+            "rokie-snapshot-{}-{}",
+            std::process::id(),
+            SNAPSHOT_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)?;
+        let snapshot = Snapshot {
+            path: dir.join(filepath.file_name().unwrap_or_default()),
+            dir,
+        };
+        std::fs::copy(filepath, &snapshot.path)?;
+        for suffix in ["-wal", "-shm"] {
+            let mut src = filepath.as_os_str().to_owned();
+            src.push(suffix);
+            let mut dst = snapshot.path.as_os_str().to_owned();
+            dst.push(suffix);
+            if Path::new(&src).exists() {
+                std::fs::copy(&src, &dst)?;
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.dir) {
+            debugln!("Failed to remove {}: {}", self.dir.display(), e);
+        }
+    }
+}
+
+/// Open a database for reading. With --snapshot, a read-only connection to
+/// a copy of the database is returned, the snapshot needs to outlive the
+/// connection.
+pub fn open_for_reading(
+    filepath: &Path,
+) -> Result<(rusqlite::Connection, Option<Snapshot>), rusqlite::Error> {
+    if !Config::global().snapshot {
+        return Ok((rusqlite::Connection::open(filepath)?, None));
+    }
+    let snapshot = Snapshot::new(filepath).map_err(|e| {
+        debugln!("Failed to create snapshot of {}: {}", filepath.display(), e);
+        rusqlite::Error::InvalidPath(filepath.to_path_buf())
+    })?;
+    let conn = rusqlite::Connection::open_with_flags(
+        &snapshot.path,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )?;
+    Ok((conn, Some(snapshot)))
+}
+
+/// Check if the database has a table with the given name,
+/// the table does not need to contain any rows
 fn is_db_with_table(conn: &rusqlite::Connection, table_name: &str) -> bool {
-    return conn
-        .query_row::<u32, _, _>(
-            &format!("SELECT 1 FROM {table_name} LIMIT 1"),
-            [],
-            |row| row.get(0),
-        )
-        .is_ok();
+    conn.query_row::<u32, _, _>(
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [table_name],
+        |row| row.get(0),
+    )
+    .is_ok()
+}
+
+/// The entries of `SEARCH_DIRS` without duplicates, in their original order
+pub fn unique_search_dirs() -> Vec<&'static str> {
+    let mut dirs: Vec<&str> = vec![];
+    for dir in SEARCH_DIRS {
+        if !dirs.contains(dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// The profiles that `identifier` refers to, an exact match on the name of a
+/// profile directory takes precedence over partial matches on the path
+pub fn matching_profiles<'a>(
+    identifier: &str,
+    cookie_dbs: &'a [CookieDB],
+) -> Vec<&'a CookieDB> {
+    let exact = cookie_dbs
+        .iter()
+        .any(|c| c.matches_profile(identifier, true));
+    cookie_dbs
+        .iter()
+        .filter(|c| c.matches_profile(identifier, exact))
+        .collect()
+}
+
+/// Lists the directories that were searched when no profiles were found
+pub fn no_profiles_message() -> String {
+    let searched: Vec<String> = unique_search_dirs()
+        .iter()
+        .map(|d| format!("  ~/{d}"))
+        .collect();
+    format!(
+        "No cookie databases found, searched:\n{}",
+        searched.join("\n")
+    )
 }
 
 /// Search all configured `SEARCH_DIRS` for SQLite databases and
 /// add each path to the provided set.
 pub fn cookie_dbs_from_profiles(cookie_dbs: &mut HashSet<CookieDB>) {
+    scan_profiles(
+        |cookie_db| {
+            cookie_dbs.insert(cookie_db);
+            true
+        },
+        |msg| errln!("{}", msg),
+    );
+}
+
+/// A database with its cookies loaded, or the reason why they could not
+/// be loaded
+pub type ScanResult = (CookieDB, Option<String>);
+
+/// Sent from a background scan, warnings cannot be written to stderr
+/// while the TUI is shown
+pub enum ScanMessage {
+    Profile(Box<ScanResult>),
+    /// A database that was already loaded before the scan
+    Known(PathBuf),
+    Warning(String),
+}
+
+/// Scan for profiles on a background thread and send each database with
+/// its cookies loaded over the returned channel, which is disconnected
+/// once the scan has finished. The databases at `known` paths are only
+/// reported, not loaded again.
+pub fn spawn_scan(known: HashSet<PathBuf>) -> Receiver<ScanMessage> {
+    let (tx, rx) = mpsc::channel::<ScanMessage>();
+    thread::spawn(move || {
+        scan_profiles(
+            |cookie_db| {
+                let msg = if known.contains(&cookie_db.path) {
+                    ScanMessage::Known(cookie_db.path)
+                } else {
+                    ScanMessage::Profile(Box::new(load_profile(cookie_db)))
+                };
+                // Stop scanning if the receiver has been dropped
+                tx.send(msg).is_ok()
+            },
+            |msg| {
+                let _ = tx.send(ScanMessage::Warning(msg));
+            },
+        );
+    });
+    rx
+}
+
+/// Load the cookies of a database for the TUI, failures are kept with the
+/// database so that they can be shown for its profile
+pub fn load_profile(mut cookie_db: CookieDB) -> ScanResult {
+    match cookie_db.load_cookies() {
+        Ok(_) => (cookie_db, None),
+        Err(e) => {
+            let reason = db_error_reason(&e);
+            (cookie_db, Some(reason))
+        }
+    }
+}
+
+/// Search all configured `SEARCH_DIRS` (or only the --profile-dir) for
+/// cookie databases and pass each database to `on_found` as soon as it is
+/// discovered. The same database can be passed several times if it is
+/// reachable from several search directories. The search stops if
+/// `on_found` returns false. Skipped databases are reported to `on_warning`.
+pub fn scan_profiles<F, W>(mut on_found: F, mut on_warning: W)
+where
+    F: FnMut(CookieDB) -> bool,
+    W: FnMut(String),
+{
+    if let Some(profile_dir) = &Config::global().profile_dir {
+        scan_dir(Path::new(profile_dir), None, &mut on_found, &mut on_warning);
+        return;
+    }
     let home = get_home();
     for search_dir in SEARCH_DIRS {
         // 'home' needs to be cloned since it is referenced in each iteration
         let search_path: String = format!("{}/{}", home.to_owned(), search_dir);
+        if !Path::new(&search_path).is_dir() {
+            debugln!("Skipping non-existent search directory: {}", search_path);
+            continue;
+        }
+        if !scan_dir(
+            Path::new(&search_path),
+            Some(search_dir),
+            &mut on_found,
+            &mut on_warning,
+        ) {
+            return;
+        }
+    }
+}
 
-        // We pass a reference of `search_path` since
-        // we want to retain ownership of the variable for later use
-        for entry in WalkDir::new(&search_path)
-            .follow_links(false)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            // The filter is used to skip inaccessible paths
-            if entry.file_type().is_file()
-                && DB_NAMES
-                    .contains(&entry.file_name().to_string_lossy().as_ref())
-            {
-                let db_type =
-                    cookie_db_type(&(entry.path())).unwrap_or_else(|_| {
-                        return DbType::Unknown;
-                    });
-                if !matches!(db_type, DbType::Unknown) {
-                    cookie_dbs.insert(CookieDB {
-                        path: entry.into_path().to_owned(),
-                        typing: db_type,
-                        cookies: vec![],
-                    });
+/// Pass each cookie database under `search_path` to `on_found`,
+/// returns false if the search was stopped by `on_found`
+fn scan_dir<F, W>(
+    search_path: &Path,
+    search_dir: Option<&'static str>,
+    on_found: &mut F,
+    on_warning: &mut W,
+) -> bool
+where
+    F: FnMut(CookieDB) -> bool,
+    W: FnMut(String),
+{
+    // Files with a name from `DB_NAMES` (or every SQLite database with
+    // --deep-scan) and the subset of those that are valid cookie databases
+    let mut candidates = 0;
+    let mut found = 0;
+
+    // With --follow-symlinks, `WalkDir` yields an error for each link
+    // that points to one of its own ancestors, which breaks any cycles.
+    for entry in WalkDir::new(search_path)
+        .follow_links(Config::global().follow_symlinks)
+        .into_iter()
+        .filter_map(|e| match e {
+            Ok(e) => Some(e),
+            Err(err) => {
+                if err.loop_ancestor().is_some() {
+                    debugln!("Skipping symlink cycle: {}", err);
                 }
+                None
+            }
+        })
+    {
+        // The filter is used to skip inaccessible paths
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if !DB_NAMES.contains(&entry.file_name().to_string_lossy().as_ref()) {
+            // Only the header is read before the insecure check, any
+            // other file is skipped without a message
+            if !Config::global().deep_scan
+                || sqlite_header(entry.path()).is_err()
+            {
+                continue;
             }
         }
+        candidates += 1;
+        // Never parse a database that could have been tampered with
+        if is_world_writable(entry.path()) {
+            if !Config::global().allow_insecure {
+                on_warning(format!(
+                    "Skipping world-writable database {} \
+                     (see --allow-insecure)",
+                    entry.path().display()
+                ));
+                continue;
+            }
+            on_warning(format!(
+                "Warning: {} is world-writable",
+                entry.path().display()
+            ));
+        }
+        // Files named like a cookie database are common, e.g. other
+        // SQLite databases, unreadable files are worth mentioning
+        let db_type = match cookie_db_type(entry.path()) {
+            Ok(db_type) => db_type,
+            Err(Error::NotADatabase(_)) => continue,
+            Err(e) => {
+                debugln!("Skipping {}", e);
+                continue;
+            }
+        };
+        found += 1;
+        let mut cookie_db = CookieDB::new(entry.into_path(), db_type);
+        cookie_db.search_dir = search_dir;
+        if !on_found(cookie_db) {
+            return false;
+        }
     }
+    debugln!(
+        "{}: {} candidate(s), {} cookie database(s)",
+        search_path.display(),
+        candidates,
+        found
+    );
+    true
 }
 
-/// Finds all SQLite databases under the given path
-/// which feature a non-empty `cookies` or `moz_cookies` table
-pub fn cookie_db_type(filepath: &Path) -> Result<DbType, io::Error> {
-    let mut f = File::open(filepath)?;
-    let mut buf = [0; 15];
-    f.read_exact(&mut buf)?;
+/// Check if an operation failed because another process, i.e. a running
+/// browser, holds a lock on the database
+pub fn is_locked(e: &rusqlite::Error) -> bool {
+    matches!(
+        e,
+        rusqlite::Error::SqliteFailure(err, _)
+            if err.code == rusqlite::ErrorCode::DatabaseBusy
+                || err.code == rusqlite::ErrorCode::DatabaseLocked
+    )
+}
 
-    if let Ok(f_header) = String::from_utf8(buf.to_vec()) {
-        if f_header != SQLITE_FILE_ID {
-            return Ok(DbType::Unknown);
-        }
+/// Describe a failed database operation on `target`, e.g.
+/// `db_error_message("load", "~/.mozilla/firefox/p1", e)`
+pub fn db_error_message(
+    action: &str,
+    target: &str,
+    e: &rusqlite::Error,
+) -> String {
+    if is_locked(e) {
+        // A snapshot only helps with reading, the browser needs to be
+        // closed before its database can be modified
+        let reason = if matches!(action, "load" | "read" | "count cookies in") {
+            db_error_reason(e)
+        } else {
+            "profile is in use by a running browser; close it and try again"
+                .to_string()
+        };
+        format!("{target}: {reason}")
+    } else {
+        format!("Failed to {action} {target}: {}", db_error_reason(e))
     }
+}
+
+/// Describe a failed modification of `target`, nothing is changed if
+/// the --audit-log could not be written
+pub fn modify_error_message(action: &str, target: &str, e: &DbError) -> String {
+    match e {
+        DbError::Sqlite(e) => db_error_message(action, target, e),
+        DbError::AuditLog(..) => format!("{target}: {e}"),
+    }
+}
 
-    if let Ok(conn) = rusqlite::Connection::open(filepath) {
+/// Why a database operation failed, without the operation or target
+pub fn db_error_reason(e: &rusqlite::Error) -> String {
+    if is_locked(e) {
+        "profile is in use by a running browser; close it or use --snapshot"
+            .to_string()
+    } else if let rusqlite::Error::InvalidColumnName(column) = e {
+        format!("missing column '{column}' (see --column-map)")
+    } else {
+        e.to_string()
+    }
+}
+
+/// Finds all SQLite databases under the given path
+/// which feature a `cookies` or `moz_cookies` table
+pub fn cookie_db_type(filepath: &Path) -> Result<DbType, Error> {
+    let not_a_db = || Error::NotADatabase(filepath.to_path_buf());
+    sqlite_header(filepath)?;
+
+    if let Ok((conn, _snapshot)) = open_for_reading(filepath) {
         if is_db_with_table(&conn, "moz_cookies") {
             conn.close().unwrap();
             return Ok(DbType::Firefox);
@@ -123,67 +569,227 @@ pub fn cookie_db_type(filepath: &Path) -> Result<DbType, io::Error> {
         }
     }
 
-    return Ok(DbType::Unknown);
+    Err(not_a_db())
+}
+
+/// Check that a file starts with the header of an SQLite database
+fn sqlite_header(filepath: &Path) -> Result<(), Error> {
+    let not_a_db = || Error::NotADatabase(filepath.to_path_buf());
+    let mut f =
+        File::open(filepath).map_err(|e| Error::with_path(filepath, e))?;
+    // The identifier is followed by a NUL byte
+    let mut buf = [0; SQLITE_FILE_ID.len() + 1];
+    f.read_exact(&mut buf).map_err(|e| match e.kind() {
+        // Shorter than the header of an SQLite database
+        io::ErrorKind::UnexpectedEof => not_a_db(),
+        _ => Error::with_path(filepath, e),
+    })?;
+
+    if buf[..SQLITE_FILE_ID.len()] != *SQLITE_FILE_ID.as_bytes()
+        || buf[SQLITE_FILE_ID.len()] != 0
+    {
+        return Err(not_a_db());
+    }
+    Ok(())
 }
 
 /// Parse the domains from a newline separated whitelist into a vector,
 /// skipping lines that start with '#'. Each entry will have explicit
 /// quotes surrounding it.
-pub fn parse_whitelist(filepath: &Path) -> Result<Vec<String>, io::Error> {
+pub fn parse_whitelist(filepath: &Path) -> Result<Vec<String>, Error> {
     let f = OpenOptions::new()
         .read(true)
         .open(filepath)
-        .expect("Failed to open whitelist");
-    let mut reader = io::BufReader::new(f);
+        .map_err(|e| Error::with_path(filepath, e))?;
+    let whitelist = parse_domain_list(io::BufReader::new(f))?
+        .iter()
+        // Insert explicit qoutes
+        .map(|d| format!("\"{d}\""))
+        .collect();
+    Ok(whitelist)
+}
 
-    let mut whitelist = vec![];
+/// Parse a --column-map file with one `browser.Field = column` entry
+/// per line, skipping blank lines and lines that start with '#'.
+/// The browser is `chrome` or `firefox` and the field a key of
+/// `COOKIE_FIELDS`. Column names are interpolated into queries and
+/// may only contain alphanumeric characters and underscores.
+pub fn parse_column_map(
+    content: &str,
+) -> Result<HashMap<String, String>, String> {
+    let mut column_map = HashMap::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, column)) = line.split_once('=') else {
+            return Err(format!(
+                "line {}: expected `browser.Field = column`",
+                i + 1
+            ));
+        };
+        let (key, column) = (key.trim(), column.trim());
+        let valid_key = key.split_once('.').is_some_and(|(browser, field)| {
+            ["chrome", "firefox"].contains(&browser)
+                && COOKIE_FIELDS.contains_key(field)
+        });
+        if !valid_key {
+            return Err(format!("line {}: unknown field '{}'", i + 1, key));
+        }
+        if column.is_empty()
+            || !column
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(format!("line {}: invalid column '{}'", i + 1, column));
+        }
+        column_map.insert(key.to_string(), column.to_string());
+    }
+    Ok(column_map)
+}
+
+/// Parse newline separated domains into a vector,
+/// skipping blank lines and lines that start with '#'.
+/// Domains are quoted in queries and may not contain quotes or whitespace.
+pub fn parse_domain_list<R: BufRead>(
+    mut reader: R,
+) -> Result<Vec<String>, Error> {
+    let mut domains = vec![];
     let mut line: String = "".to_string();
+    let mut line_number = 0;
     while reader.read_line(&mut line)? > 0 {
+        line_number += 1;
         // Skip comments
         let trimmed_line = line.trim();
-        if !trimmed_line.starts_with("#") && trimmed_line.len() > 0 {
-            // Insert explicit qoutes
-            whitelist.push(format!("\"{trimmed_line}\""));
+        if !trimmed_line.starts_with("#") && !trimmed_line.is_empty() {
+            if trimmed_line
+                .chars()
+                .any(|c| c.is_whitespace() || c == '"' || c == '\'')
+            {
+                return Err(Error::InvalidDomain {
+                    line: line_number,
+                    domain: trimmed_line.to_string(),
+                });
+            }
+            domains.push(trimmed_line.to_string());
         }
         line = "".to_string();
     }
-    Ok(whitelist)
+    Ok(domains)
 }
 
-/// Only applies if `SSH_CONNECTION` is unset.
-/// Utilises `xsel` on Linux/BSD.
-pub fn copy_to_clipboard(content: String) -> Result<(), io::Error> {
-    if std::env::var("SSH_CONNECTION").is_ok() {
-        return Ok(());
-    }
-    match consts::OS {
-        "macos" => {
-            let mut p = Command::new("/usr/bin/pbcopy")
-                .stdin(Stdio::piped())
-                .spawn()?;
+/// The site of a host, i.e. its registrable domain according to the
+/// public suffix list, e.g. `example.co.uk` for `.www.example.co.uk`.
+/// Hosts without a registrable domain, e.g. `localhost`, are kept as is.
+pub fn site(host: &str) -> &str {
+    let host = host.trim_start_matches('.');
+    psl::domain_str(host).unwrap_or(host)
+}
 
-            p.stdin.as_mut().unwrap().write_all(content.as_bytes())
+/// Parse a duration such as `7d`, `24h` or `1w2d` into seconds, the units
+/// are `s`, `m`, `h`, `d` and `w`
+pub fn parse_duration(s: &str) -> Result<i64, String> {
+    let mut total: i64 = 0;
+    let mut digits = String::new();
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
         }
-        "linux" | "freebsd" => {
-            if std::env::var("DISPLAY").is_ok() {
-                let mut p = Command::new("xsel")
-                    .args(["-i", "-b"])
-                    .stdin(Stdio::piped())
-                    .spawn()?;
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return Err(format!("invalid unit '{c}' in '{s}'")),
+        };
+        let n: i64 = digits
+            .parse()
+            .map_err(|_| format!("missing number before '{c}' in '{s}'"))?;
+        total = n
+            .checked_mul(unit)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(|| format!("duration '{s}' is too large"))?;
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        return Err(format!("missing unit after '{digits}' in '{s}'"));
+    }
+    if total == 0 {
+        return Err(format!("invalid duration '{s}'"));
+    }
+    Ok(total)
+}
 
-                p.stdin.as_mut().unwrap().write_all(content.as_bytes())
-            } else {
-                Ok(())
-            }
+/// Load the domains that are pinned in the TUI from `PINNED_FILE`,
+/// no domains are pinned if the file does not exist
+pub fn load_pinned() -> Result<HashSet<String>, Error> {
+    let path = app_dir(CONFIG_DIR).join(PINNED_FILE);
+    match File::open(path) {
+        Ok(f) => Ok(parse_domain_list(io::BufReader::new(f))?
+            .into_iter()
+            .collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HashSet::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Write the pinned domains to `PINNED_FILE`, one domain per line
+pub fn save_pinned(pinned: &HashSet<String>) -> Result<(), io::Error> {
+    let path = app_dir(CONFIG_DIR).join(PINNED_FILE);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut domains = Vec::from_iter(pinned);
+    domains.sort();
+    let mut content = String::new();
+    for domain in domains {
+        content.push_str(domain);
+        content.push('\n');
+    }
+    std::fs::write(path, content)
+}
+
+/// Open a writer for command output, an empty `filepath` writes to stdout.
+/// Parent directories of `filepath` are created as needed and an existing
+/// file is only overwritten if `force` is set.
+pub fn output_writer(
+    filepath: &str,
+    force: bool,
+) -> Result<Box<dyn Write>, Error> {
+    if filepath.is_empty() {
+        return Ok(Box::new(io::stdout()));
+    }
+    let filepath = Path::new(filepath);
+    if filepath.exists() && !force {
+        return Err(Error::AlreadyExists(filepath.to_path_buf()));
+    }
+    if let Some(parent) = filepath.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| Error::with_path(parent, e))?;
         }
-        _ => Ok(()),
     }
+    let f =
+        File::create(filepath).map_err(|e| Error::with_path(filepath, e))?;
+    Ok(Box::new(io::BufWriter::new(f)))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::util::{cookie_db_type, DbType};
-    use std::path::Path;
+    use crate::config::{Config, CONFIG};
+    use crate::cookie_db::CookieDB;
+    use crate::error::Error;
+    use crate::fixtures::TempDir;
+    use crate::util::{
+        browser_process, cookie_db_type, db_error_message, is_world_writable,
+        matching_profiles, parse_column_map, parse_domain_list, parse_duration,
+        site, sqlite_header, xdg_dir, DbType,
+    };
+    use std::ffi::OsString;
+    use std::path::{Path, PathBuf};
 
     #[test]
     fn test_is_cookie_db() {
@@ -192,4 +798,240 @@ mod tests {
             assert!(matches!(result.unwrap(), DbType::Firefox));
         }
     }
+
+    #[test]
+    fn test_empty_cookie_db() {
+        CONFIG.get_or_init(Config::default);
+        let dir = TempDir::new("empty");
+
+        // Cookie tables without any rows are still recognized
+        let firefox = dir
+            .sqlite("cookies.sqlite", "CREATE TABLE moz_cookies (id INTEGER);");
+        let chrome =
+            dir.sqlite("Cookies", "CREATE TABLE cookies (host_key TEXT);");
+        let other =
+            dir.sqlite("other.sqlite", "CREATE TABLE other (id INTEGER);");
+
+        assert!(matches!(cookie_db_type(&firefox).unwrap(), DbType::Firefox));
+        assert!(matches!(cookie_db_type(&chrome).unwrap(), DbType::Chrome));
+        assert!(matches!(
+            cookie_db_type(&other),
+            Err(Error::NotADatabase(_))
+        ));
+        assert!(matches!(
+            cookie_db_type(&dir.path().join("missing")),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_is_world_writable() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = TempDir::new("permissions");
+        let dir = tmp.path();
+        let path = dir.join("Cookies");
+        std::fs::write(&path, "").unwrap();
+        let set_mode = |p: &Path, mode: u32| {
+            std::fs::set_permissions(p, PermissionsExt::from_mode(mode))
+                .unwrap()
+        };
+
+        set_mode(dir, 0o755);
+        set_mode(&path, 0o644);
+        assert!(!is_world_writable(&path));
+        set_mode(&path, 0o666);
+        assert!(is_world_writable(&path));
+        set_mode(&path, 0o644);
+        set_mode(dir, 0o777);
+        assert!(is_world_writable(&path));
+        set_mode(dir, 0o1777);
+        assert!(!is_world_writable(&path));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Ok(90));
+        assert_eq!(parse_duration("24h"), Ok(86400));
+        assert_eq!(parse_duration("1w2d"), Ok(9 * 86400));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("7").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("3y").is_err());
+        assert!(parse_duration("99999999999999999w").is_err());
+    }
+
+    #[test]
+    fn test_parse_domain_list() {
+        let domains = parse_domain_list(
+            "# Comment\n.example.com\n\n a.org \n".as_bytes(),
+        );
+        assert_eq!(domains.unwrap(), vec![".example.com", "a.org"]);
+        let invalid = parse_domain_list("a.org\nb.org\" OR 1\n".as_bytes());
+        assert!(matches!(invalid, Err(Error::InvalidDomain { line: 2, .. })));
+    }
+
+    #[test]
+    fn test_xdg_dir() {
+        let state = |value: Option<&str>| {
+            xdg_dir(value.map(OsString::from), "/home/u", ".local/state")
+        };
+        assert_eq!(state(None), Path::new("/home/u/.local/state"));
+        assert_eq!(state(Some("/var/state")), Path::new("/var/state"));
+        // Relative paths are invalid
+        assert_eq!(state(Some("state")), Path::new("/home/u/.local/state"));
+        assert_eq!(state(Some("")), Path::new("/home/u/.local/state"));
+    }
+
+    #[test]
+    fn test_browser_process() {
+        let browser = |name: &str, exe: &str, cmd: &[&str]| {
+            let cmd: Vec<String> = cmd.iter().map(|a| a.to_string()).collect();
+            browser_process(name, Path::new(exe), &cmd).map(|(b, _)| b)
+        };
+        assert_eq!(browser("firefox", "", &[]), Some("Firefox"));
+        assert_eq!(
+            browser("firefox-bin", "/usr/lib/firefox/firefox-bin", &[]),
+            Some("Firefox")
+        );
+        // The name is truncated to 15 characters on Linux
+        assert_eq!(
+            browser(
+                "chromium-browse",
+                "/usr/lib/chromium/chromium",
+                &["/usr/bin/chromium-browser"]
+            ),
+            Some("Chromium")
+        );
+        assert_eq!(
+            browser("MainThread", "", &["/opt/google/chrome/chrome"]),
+            Some("Chrome")
+        );
+        assert_eq!(browser("firefox.exe", "", &[]), Some("Firefox"));
+        assert_eq!(
+            browser(
+                "Brave Browser",
+                "/Applications/Brave Browser.app/Contents/MacOS/Brave Browser",
+                &[]
+            ),
+            Some("Brave")
+        );
+        assert_eq!(
+            browser(
+                "bwrap",
+                "/usr/bin/bwrap",
+                &["bwrap", "--args", "40", "org.mozilla.firefox"]
+            ),
+            Some("Firefox")
+        );
+        // Only the Flatpak sandbox is matched by the ID
+        assert_eq!(browser("vim", "", &["vim", "org.mozilla.firefox"]), None);
+        assert_eq!(browser("firefox-sync", "", &[]), None);
+        assert_eq!(browser("bash", "/usr/bin/bash", &["bash"]), None);
+        assert_eq!(
+            browser_process("brave", Path::new(""), &[]),
+            Some(("Brave", DbType::Chrome))
+        );
+    }
+
+    #[test]
+    fn test_matching_profiles() {
+        CONFIG.get_or_init(Config::default);
+        let cookie_dbs: Vec<CookieDB> = [
+            "/home/user/.mozilla/firefox/default/cookies.sqlite",
+            "/home/user/.mozilla/firefox/default-release/cookies.sqlite",
+            "/home/user/.config/chromium/Default/Network/Cookies",
+        ]
+        .iter()
+        .map(|p| CookieDB::new(PathBuf::from(p), DbType::Firefox))
+        .collect();
+        let paths = |identifier| -> Vec<&Path> {
+            matching_profiles(identifier, &cookie_dbs)
+                .iter()
+                .map(|c| c.path.as_path())
+                .collect()
+        };
+
+        // An exact profile name wins over partial matches
+        assert_eq!(paths("default"), vec![cookie_dbs[0].path.as_path()]);
+        assert_eq!(paths("Default"), vec![cookie_dbs[2].path.as_path()]);
+        // Partial matches are ambiguous when more than one path matches
+        assert_eq!(paths("firefox/def").len(), 2);
+        assert_eq!(paths("release"), vec![cookie_dbs[1].path.as_path()]);
+        assert!(paths("safari").is_empty());
+    }
+
+    #[test]
+    fn test_sqlite_header() {
+        let dir = TempDir::new("header");
+        let db_path =
+            dir.sqlite("cookies.sqlite", "CREATE TABLE t (id INTEGER);");
+        assert!(sqlite_header(&db_path).is_ok());
+
+        // Binary files that are not valid UTF-8 are not databases either
+        let cases: [(&str, &[u8]); 3] = [
+            (
+                "binary",
+                &[
+                    0xff, 0xd8, 0xff, 0xe0, 0, 0x10, 0x4a, 0x46, 0x49, 0x46, 0,
+                    1, 1, 0, 0, 1, 0, 1,
+                ],
+            ),
+            ("no-nul", b"SQLite format 3 and more"),
+            ("short", b"SQLite"),
+        ];
+        for (name, content) in cases {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            assert!(
+                matches!(sqlite_header(&path), Err(Error::NotADatabase(_))),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_db_error_message() {
+        let locked = rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            None,
+        );
+        // Snapshots are only suggested when reading
+        assert!(db_error_message("load", "p1", &locked).contains("--snapshot"));
+        assert!(
+            !db_error_message("delete", "p1", &locked).contains("--snapshot")
+        );
+
+        let missing = rusqlite::Error::InvalidColumnName("host".to_string());
+        assert_eq!(
+            db_error_message("load", "p1", &missing),
+            "Failed to load p1: missing column 'host' (see --column-map)"
+        );
+    }
+
+    #[test]
+    fn test_site() {
+        assert_eq!(site(".www.example.com"), "example.com");
+        assert_eq!(site("login.example.co.uk"), "example.co.uk");
+        assert_eq!(site("example.com"), "example.com");
+        assert_eq!(site("localhost"), "localhost");
+    }
+
+    #[test]
+    fn test_parse_column_map() {
+        let column_map = parse_column_map(
+            "# Fork with a renamed host column\nchrome.Host = host\n\n\
+             firefox.LastAccess=last_accessed\n",
+        )
+        .unwrap();
+        assert_eq!(column_map.len(), 2);
+        assert_eq!(column_map["chrome.Host"], "host");
+        assert_eq!(column_map["firefox.LastAccess"], "last_accessed");
+
+        assert!(parse_column_map("chrome.Host").is_err());
+        assert!(parse_column_map("safari.Host = host").is_err());
+        assert!(parse_column_map("chrome.Domain = host").is_err());
+        assert!(parse_column_map("chrome.Host = host; DROP").is_err());
+        assert!(parse_column_map("chrome.Host =").is_err());
+    }
 }
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Files next to a database that the browser writes to, the `-shm` file
+/// is left out since it also changes when the database is only read
+const WRITE_SUFFIXES: &[&str] = &["", "-wal", "-journal"];
+
+/// Watches cookie databases for writes by the browser, see `tui --watch`.
+/// The directory of each database is watched since Firefox only writes
+/// to the `-wal` file until it is checkpointed.
+pub struct DbWatcher {
+    watcher: RecommendedWatcher,
+    rx: Receiver<PathBuf>,
+    watched: Vec<PathBuf>,
+    /// Databases with unhandled writes and the time of the latest write
+    changes: HashMap<PathBuf, Instant>,
+    debounce: Duration,
+}
+
+impl DbWatcher {
+    pub fn new(debounce: Duration) -> Result<Self, notify::Error> {
+        let (tx, rx) = mpsc::channel();
+        let watcher = notify::recommended_watcher(
+            move |event: notify::Result<Event>| {
+                // Reads also trigger access events
+                if let Ok(event) = event {
+                    if matches!(
+                        event.kind,
+                        EventKind::Modify(ModifyKind::Data(_))
+                            | EventKind::Modify(ModifyKind::Any)
+                    ) {
+                        event.paths.into_iter().for_each(|p| {
+                            let _ = tx.send(p);
+                        });
+                    }
+                }
+            },
+        )?;
+        Ok(DbWatcher {
+            watcher,
+            rx,
+            watched: vec![],
+            changes: HashMap::new(),
+            debounce,
+        })
+    }
+
+    /// Watch `paths` instead of the databases that are currently watched,
+    /// nothing is done if they are the same
+    pub fn watch(&mut self, paths: &[PathBuf]) -> Result<(), notify::Error> {
+        if self.watched == paths {
+            return Ok(());
+        }
+        for path in self.watched.drain(..) {
+            if let Some(dir) = path.parent() {
+                // The directory can be shared with another database
+                let _ = self.watcher.unwatch(dir);
+            }
+        }
+        self.changes.clear();
+        // Failures are only reported once for the same databases
+        self.watched = paths.to_vec();
+        for path in paths {
+            if let Some(dir) = path.parent() {
+                self.watcher.watch(dir, RecursiveMode::NonRecursive)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Databases that were written to and have not changed for the
+    /// debounce duration since, each write is only reported once
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        while let Ok(path) = self.rx.try_recv() {
+            if let Some(db) =
+                self.watched.iter().find(|db| is_written_file(db, &path))
+            {
+                self.changes.insert(db.clone(), now);
+            }
+        }
+        let ready: Vec<PathBuf> = self
+            .changes
+            .iter()
+            .filter(|(_, t)| now.duration_since(**t) >= self.debounce)
+            .map(|(p, _)| p.clone())
+            .collect();
+        ready.iter().for_each(|p| {
+            self.changes.remove(p);
+        });
+        ready
+    }
+}
+
+/// Check if `path` is the database at `db` or one of its journals
+fn is_written_file(db: &Path, path: &Path) -> bool {
+    WRITE_SUFFIXES.iter().any(|suffix| {
+        let mut name = db.as_os_str().to_owned();
+        name.push(suffix);
+        path == Path::new(&name)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::watch::is_written_file;
+
+    #[test]
+    fn test_is_written_file() {
+        let db = Path::new("/p/cookies.sqlite");
+        assert!(is_written_file(db, Path::new("/p/cookies.sqlite")));
+        assert!(is_written_file(db, Path::new("/p/cookies.sqlite-wal")));
+        assert!(!is_written_file(db, Path::new("/p/cookies.sqlite-shm")));
+        assert!(!is_written_file(db, Path::new("/p/places.sqlite")));
+    }
+}